@@ -0,0 +1,205 @@
+// Decodes `emit!` payloads (8 byte discriminator + borsh) into the programs' event types.
+// Both programs have `Staked`/`Unstaked` events with the same discriminator, so the
+// emitting program id decides which layout is used.
+
+use anchor_lang::{prelude::Pubkey, AnchorDeserialize, Discriminator};
+
+use crate::logs::ProgramData;
+
+pub enum SolEvent {
+    ConfigInitialized(staking_contract::ConfigInitialized),
+    SeasonEnded(staking_contract::SeasonEnded),
+    StakeAccountInitialized(staking_contract::StakeAccountInitialized),
+    Staked(staking_contract::Staked),
+    Unstaked(staking_contract::Unstaked),
+    PointsClaimed(staking_contract::PointsClaimed),
+    PointsUpdated(staking_contract::PointsUpdated),
+    SeasonRolled(staking_contract::SeasonRolled),
+}
+
+pub enum TokenEvent {
+    Initialized(staking_token_contract::Initialized),
+    Staked(staking_token_contract::Staked),
+    StakedFor(staking_token_contract::StakedFor),
+    GrantStaked(staking_token_contract::GrantStaked),
+    GrantClaimed(staking_token_contract::GrantClaimed),
+    Unstaked(staking_token_contract::Unstaked),
+    RewardsClaimed(staking_token_contract::RewardsClaimed),
+    RewardsCompounded(staking_token_contract::RewardsCompounded),
+    PositionTransferred(staking_token_contract::PositionTransferred),
+    Slashed(staking_token_contract::Slashed),
+    DistributorCreated(staking_token_contract::DistributorCreated),
+    AirdropClaimed(staking_token_contract::AirdropClaimed),
+    AirdropClawedBack(staking_token_contract::AirdropClawedBack),
+    ConfigChangeExecuted(staking_token_contract::ConfigChangeExecuted),
+}
+
+pub enum IndexedEvent {
+    Sol(SolEvent),
+    Token(TokenEvent),
+}
+
+impl IndexedEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexedEvent::Sol(event) => match event {
+                SolEvent::ConfigInitialized(_) => "ConfigInitialized",
+                SolEvent::SeasonEnded(_) => "SeasonEnded",
+                SolEvent::StakeAccountInitialized(_) => "StakeAccountInitialized",
+                SolEvent::Staked(_) => "Staked",
+                SolEvent::Unstaked(_) => "Unstaked",
+                SolEvent::PointsClaimed(_) => "PointsClaimed",
+                SolEvent::PointsUpdated(_) => "PointsUpdated",
+                SolEvent::SeasonRolled(_) => "SeasonRolled",
+            },
+            IndexedEvent::Token(event) => match event {
+                TokenEvent::Initialized(_) => "Initialized",
+                TokenEvent::Staked(_) => "Staked",
+                TokenEvent::StakedFor(_) => "StakedFor",
+                TokenEvent::GrantStaked(_) => "GrantStaked",
+                TokenEvent::GrantClaimed(_) => "GrantClaimed",
+                TokenEvent::Unstaked(_) => "Unstaked",
+                TokenEvent::RewardsClaimed(_) => "RewardsClaimed",
+                TokenEvent::RewardsCompounded(_) => "RewardsCompounded",
+                TokenEvent::PositionTransferred(_) => "PositionTransferred",
+                TokenEvent::Slashed(_) => "Slashed",
+                TokenEvent::DistributorCreated(_) => "DistributorCreated",
+                TokenEvent::AirdropClaimed(_) => "AirdropClaimed",
+                TokenEvent::AirdropClawedBack(_) => "AirdropClawedBack",
+                TokenEvent::ConfigChangeExecuted(_) => "ConfigChangeExecuted",
+            },
+        }
+    }
+
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            IndexedEvent::Sol(_) => staking_contract::ID,
+            IndexedEvent::Token(_) => staking_token_contract::ID,
+        }
+    }
+}
+
+fn decode<T: Discriminator + AnchorDeserialize>(data: &[u8]) -> Option<T> {
+    let payload = data.strip_prefix(T::DISCRIMINATOR)?;
+    T::try_from_slice(payload).ok()
+}
+
+// tries every event type of the program until one matches the discriminator
+macro_rules! decode_any {
+    ($data:expr, $wrap:ident, $program:ident, [$($event:ident),* $(,)?]) => {
+        $(
+            if let Some(event) = decode::<$program::$event>($data) {
+                return Some($wrap::$event(event));
+            }
+        )*
+    };
+}
+
+fn decode_sol_event(data: &[u8]) -> Option<SolEvent> {
+    decode_any!(data, SolEvent, staking_contract, [
+        ConfigInitialized,
+        SeasonEnded,
+        StakeAccountInitialized,
+        Staked,
+        Unstaked,
+        PointsClaimed,
+        PointsUpdated,
+        SeasonRolled,
+    ]);
+    None
+}
+
+fn decode_token_event(data: &[u8]) -> Option<TokenEvent> {
+    decode_any!(data, TokenEvent, staking_token_contract, [
+        Initialized,
+        Staked,
+        StakedFor,
+        GrantStaked,
+        GrantClaimed,
+        Unstaked,
+        RewardsClaimed,
+        RewardsCompounded,
+        PositionTransferred,
+        Slashed,
+        DistributorCreated,
+        AirdropClaimed,
+        AirdropClawedBack,
+        ConfigChangeExecuted,
+    ]);
+    None
+}
+
+pub fn decode_event(program_data: &ProgramData) -> Option<IndexedEvent> {
+    if program_data.program_id == staking_contract::ID {
+        decode_sol_event(&program_data.data).map(IndexedEvent::Sol)
+    } else if program_data.program_id == staking_token_contract::ID {
+        decode_token_event(&program_data.data).map(IndexedEvent::Token)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    fn clawback() -> staking_token_contract::AirdropClawedBack {
+        staking_token_contract::AirdropClawedBack {
+            distributor: Pubkey::new_unique(),
+            season: 3,
+            amount: 500,
+            destination: Pubkey::new_unique(),
+            slot: 42,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn decodes_a_token_event_by_its_discriminator() {
+        let event = clawback();
+
+        let Some(TokenEvent::AirdropClawedBack(decoded)) = decode_token_event(&event.data()) else {
+            panic!("expected AirdropClawedBack");
+        };
+
+        assert_eq!(decoded.distributor, event.distributor);
+        assert_eq!(decoded.season, 3);
+        assert_eq!(decoded.amount, 500);
+        assert_eq!(decoded.destination, event.destination);
+    }
+
+    #[test]
+    fn rejects_unknown_discriminators_and_truncated_payloads() {
+        let data = clawback().data();
+
+        assert!(decode_token_event(&[0; 8]).is_none());
+        assert!(decode_token_event(&data[..data.len() - 1]).is_none());
+        assert!(decode_token_event(&[]).is_none());
+    }
+
+    #[test]
+    fn picks_the_layout_from_the_emitting_program() {
+        let staked = staking_token_contract::Staked {
+            user: Pubkey::new_unique(),
+            amount: 10,
+            rewards_paid: 0,
+            staked_amount: 10,
+            slot: 1,
+            timestamp: 1,
+        };
+
+        let from = |program_id| ProgramData {
+            program_id,
+            data: staked.data(),
+        };
+
+        let decoded = decode_event(&from(staking_token_contract::ID)).unwrap();
+        assert!(matches!(
+            decoded,
+            IndexedEvent::Token(TokenEvent::Staked(_))
+        ));
+
+        assert!(decode_event(&from(Pubkey::new_unique())).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+// Turns transaction logs into the raw `Program data:` payloads, attributed to the
+// program that emitted them.
+//
+// Two inputs are understood:
+// 1) JSON-RPC dumps - `getTransaction` responses (or just their `result`), as a
+//    single value, an array, or one value per line
+// 2) plain log text - e.g. the output of `solana logs`, or bare `Program ...` lines
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use std::str::FromStr;
+
+pub struct RawTransaction {
+    pub signature: String,
+    pub slot: Option<u64>,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+    pub logs: Vec<String>,
+}
+
+pub struct ProgramData {
+    pub program_id: Pubkey,
+    pub data: Vec<u8>,
+}
+
+pub fn read_transactions(path: &str) -> Result<Vec<RawTransaction>> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("reading {}", path))?;
+    let trimmed = content.trim_start();
+
+    if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        parse_json_dump(&content)
+    } else {
+        Ok(parse_log_text(&content, path))
+    }
+}
+
+fn parse_json_dump(content: &str) -> Result<Vec<RawTransaction>> {
+    let values: Vec<Value> = match serde_json::from_str::<Value>(content) {
+        Ok(Value::Array(values)) => values,
+        Ok(value) => vec![value],
+        // JSON lines
+        Err(_) => content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<_, _>>()
+            .context("parsing JSON-RPC dump")?,
+    };
+
+    Ok(values.iter().filter_map(transaction_from_json).collect())
+}
+
+fn transaction_from_json(value: &Value) -> Option<RawTransaction> {
+    // full JSON-RPC response or just the result
+    let tx = value.get("result").unwrap_or(value);
+    let meta = tx.get("meta")?;
+
+    let logs = meta
+        .get("logMessages")?
+        .as_array()?
+        .iter()
+        .filter_map(|log| log.as_str().map(str::to_string))
+        .collect();
+
+    let signature = tx
+        .pointer("/transaction/signatures/0")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    Some(RawTransaction {
+        signature,
+        slot: tx.get("slot").and_then(Value::as_u64),
+        block_time: tx.get("blockTime").and_then(Value::as_i64),
+        failed: !meta.get("err").is_none_or(Value::is_null),
+        logs,
+    })
+}
+
+fn parse_log_text(content: &str, path: &str) -> Vec<RawTransaction> {
+    let mut transactions = Vec::new();
+    let mut current: Option<RawTransaction> = None;
+
+    for line in content.lines().map(str::trim) {
+        // `solana logs` header: "Transaction executed in slot 123:"
+        if let Some(rest) = line.strip_prefix("Transaction executed in slot ") {
+            transactions.extend(current.take());
+            current = Some(RawTransaction {
+                signature: String::new(),
+                slot: rest.trim_end_matches(':').parse().ok(),
+                block_time: None,
+                failed: false,
+                logs: Vec::new(),
+            });
+            continue;
+        }
+
+        let tx = current.get_or_insert_with(|| RawTransaction {
+            signature: String::new(),
+            slot: None,
+            block_time: None,
+            failed: false,
+            logs: Vec::new(),
+        });
+
+        if let Some(signature) = line.strip_prefix("Signature: ") {
+            tx.signature = signature.to_string();
+        } else if let Some(status) = line.strip_prefix("Status: ") {
+            tx.failed = status != "Ok";
+        } else if line.starts_with("Program ") {
+            tx.logs.push(line.to_string());
+        }
+    }
+
+    transactions.extend(current);
+
+    // plain logs have no signature, key them by file position so re-ingesting is idempotent
+    for (i, tx) in transactions.iter_mut().enumerate() {
+        if tx.signature.is_empty() {
+            tx.signature = format!("{}#{}", path, i);
+        }
+    }
+
+    transactions
+}
+
+// Walks the invoke stack so every `Program data:` line is attributed to the
+// program that was executing when it was logged
+pub fn program_data(logs: &[String]) -> Vec<ProgramData> {
+    let mut stack: Vec<Pubkey> = Vec::new();
+    let mut out = Vec::new();
+
+    for log in logs {
+        if let Some(data) = log.strip_prefix("Program data: ") {
+            let (Some(program_id), Ok(data)) = (stack.last(), STANDARD.decode(data.trim())) else {
+                continue;
+            };
+
+            out.push(ProgramData {
+                program_id: *program_id,
+                data,
+            });
+            continue;
+        }
+
+        let mut parts = log.split_whitespace();
+
+        let (Some("Program"), Some(id), Some(action)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+
+        match action {
+            "invoke" => {
+                if let Ok(program_id) = Pubkey::from_str(id) {
+                    stack.push(program_id);
+                }
+            }
+            "success" | "failed:" => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attributes_data_to_the_program_on_top_of_the_stack() {
+        let outer = Pubkey::new_unique();
+        let inner = Pubkey::new_unique();
+
+        let logs = vec![
+            format!("Program {} invoke [1]", outer),
+            format!("Program data: {}", STANDARD.encode([1])),
+            format!("Program {} invoke [2]", inner),
+            format!("Program data: {}", STANDARD.encode([2])),
+            format!("Program {} success", inner),
+            format!("Program data: {}", STANDARD.encode([3])),
+            format!("Program {} success", outer),
+        ];
+
+        let out = program_data(&logs);
+        let attributed: Vec<_> = out.iter().map(|d| (d.program_id, d.data.clone())).collect();
+
+        assert_eq!(
+            attributed,
+            vec![(outer, vec![1]), (inner, vec![2]), (outer, vec![3])]
+        );
+    }
+
+    #[test]
+    fn pops_failed_invokes_and_skips_data_outside_any_program() {
+        let outer = Pubkey::new_unique();
+        let inner = Pubkey::new_unique();
+
+        let logs = vec![
+            format!("Program data: {}", STANDARD.encode([0])),
+            format!("Program {} invoke [1]", outer),
+            format!("Program {} invoke [2]", inner),
+            format!("Program {} failed: custom program error: 0x1", inner),
+            format!("Program data: {}", STANDARD.encode([1])),
+            "Program data: not base64!".to_string(),
+            format!("Program {} consumed 1000 of 200000 compute units", outer),
+            format!("Program {} success", outer),
+            format!("Program data: {}", STANDARD.encode([2])),
+        ];
+
+        let out = program_data(&logs);
+
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].program_id, outer);
+        assert_eq!(out[0].data, vec![1]);
+    }
+
+    fn rpc_response(signature: &str, slot: u64, err: Value) -> Value {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "slot": slot,
+                "blockTime": 1_700_000_000,
+                "meta": { "err": err, "logMessages": ["Program log: hi"] },
+                "transaction": { "signatures": [signature] }
+            }
+        })
+    }
+
+    #[test]
+    fn parses_a_single_json_response() {
+        let content = rpc_response("sig1", 10, Value::Null).to_string();
+
+        let txs = parse_json_dump(&content).unwrap();
+
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].signature, "sig1");
+        assert_eq!(txs[0].slot, Some(10));
+        assert_eq!(txs[0].block_time, Some(1_700_000_000));
+        assert!(!txs[0].failed);
+        assert_eq!(txs[0].logs, vec!["Program log: hi"]);
+    }
+
+    #[test]
+    fn parses_json_arrays_of_results_and_json_lines() {
+        let failed = rpc_response(
+            "sig2",
+            11,
+            serde_json::json!({ "InstructionError": [0, "Custom"] }),
+        );
+        // a bare `result` is accepted as well
+        let array = Value::Array(vec![
+            rpc_response("sig1", 10, Value::Null)["result"].clone(),
+            failed.clone(),
+        ]);
+
+        let txs = parse_json_dump(&array.to_string()).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].signature, "sig1");
+        assert!(txs[1].failed);
+
+        let lines = format!("{}\n\n{}\n", rpc_response("sig1", 10, Value::Null), failed);
+
+        let txs = parse_json_dump(&lines).unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[1].signature, "sig2");
+        assert_eq!(txs[1].slot, Some(11));
+    }
+
+    #[test]
+    fn parses_solana_logs_output() {
+        let content = "\
+Transaction executed in slot 5:
+  Signature: sig1
+  Status: Ok
+  Log Messages:
+    Program 11111111111111111111111111111111 invoke [1]
+    Program 11111111111111111111111111111111 success
+Transaction executed in slot 6:
+  Signature: sig2
+  Status: Error processing Instruction 0: custom program error: 0x1
+  Log Messages:
+    Program 11111111111111111111111111111111 invoke [1]
+";
+
+        let txs = parse_log_text(content, "logs.txt");
+
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0].signature, "sig1");
+        assert_eq!(txs[0].slot, Some(5));
+        assert!(!txs[0].failed);
+        assert_eq!(txs[0].logs.len(), 2);
+        assert_eq!(txs[1].signature, "sig2");
+        assert!(txs[1].failed);
+    }
+
+    #[test]
+    fn keys_bare_log_lines_by_file_position() {
+        let content = "Program 11111111111111111111111111111111 invoke [1]\nsome other line\n";
+
+        let txs = parse_log_text(content, "logs.txt");
+
+        assert_eq!(txs.len(), 1);
+        assert_eq!(txs[0].signature, "logs.txt#0");
+        assert_eq!(txs[0].slot, None);
+        assert_eq!(txs[0].logs.len(), 1);
+    }
+}
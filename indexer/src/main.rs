@@ -0,0 +1,116 @@
+// Off-chain indexer for staking_contract and staking_token_contract.
+//
+// Reads transaction logs, decodes the programs' events and rebuilds per-user and
+// per-pool state in SQLite, so the backend doesn't have to poll every PDA.
+//
+//   staking-indexer <db> ingest <file>...         logs or JSON-RPC `getTransaction` dumps
+//   staking-indexer <db> leaderboard sol [limit]  points earned (claimed + unclaimed)
+//   staking-indexer <db> leaderboard season <n> [limit]
+//   staking-indexer <db> leaderboard token [limit]
+//   staking-indexer <db> tvl <sol|token>
+
+mod events;
+mod logs;
+mod store;
+
+use anyhow::{bail, Context, Result};
+
+use store::{LeaderboardRow, Store, SOL_POOL, TOKEN_POOL};
+
+const DEFAULT_LIMIT: u32 = 20;
+
+const USAGE: &str = "usage:
+  staking-indexer <db> ingest <file>...
+  staking-indexer <db> leaderboard sol [limit]
+  staking-indexer <db> leaderboard season <season> [limit]
+  staking-indexer <db> leaderboard token [limit]
+  staking-indexer <db> tvl <sol|token>";
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let (Some(db_path), Some(command)) = (args.first(), args.get(1)) else {
+        bail!(USAGE);
+    };
+
+    let mut store = Store::open(db_path).with_context(|| format!("opening {}", db_path))?;
+    let rest = &args[2..];
+
+    match command.as_str() {
+        "ingest" => ingest(&mut store, rest),
+        "leaderboard" => leaderboard(&store, rest),
+        "tvl" => tvl(&store, rest),
+        _ => bail!(USAGE),
+    }
+}
+
+fn ingest(store: &mut Store, files: &[String]) -> Result<()> {
+    if files.is_empty() {
+        bail!(USAGE);
+    }
+
+    for file in files {
+        let transactions = logs::read_transactions(file)?;
+        let mut applied = 0;
+
+        for tx in transactions.iter().filter(|tx| !tx.failed) {
+            let events: Vec<_> = logs::program_data(&tx.logs)
+                .into_iter()
+                .filter_map(|program_data| {
+                    let event = events::decode_event(&program_data)?;
+                    Some((program_data.data, event))
+                })
+                .collect();
+
+            applied += store.ingest(tx, &events)?;
+        }
+
+        println!("{}: {} transactions, {} new events", file, transactions.len(), applied);
+    }
+
+    Ok(())
+}
+
+fn parse_limit(arg: Option<&String>) -> Result<u32> {
+    arg.map(|limit| limit.parse().context("invalid limit"))
+        .unwrap_or(Ok(DEFAULT_LIMIT))
+}
+
+fn leaderboard(store: &Store, args: &[String]) -> Result<()> {
+    let rows = match args.first().map(String::as_str) {
+        Some("sol") => store.sol_leaderboard(parse_limit(args.get(1))?)?,
+        Some("season") => {
+            let season = args.get(1).context("missing season")?.parse().context("invalid season")?;
+            store.season_leaderboard(season, parse_limit(args.get(2))?)?
+        }
+        Some("token") => store.token_leaderboard(parse_limit(args.get(1))?)?,
+        _ => bail!(USAGE),
+    };
+
+    print_leaderboard(&rows);
+    Ok(())
+}
+
+fn print_leaderboard(rows: &[LeaderboardRow]) {
+    println!("{:>4}  {:<44}  {:>20}  {:>20}", "rank", "user", "staked", "score");
+
+    for (rank, row) in rows.iter().enumerate() {
+        println!("{:>4}  {:<44}  {:>20}  {:>20}", rank + 1, row.user, row.staked_amount, row.score);
+    }
+}
+
+fn tvl(store: &Store, args: &[String]) -> Result<()> {
+    let pool = match args.first().map(String::as_str) {
+        Some("sol") => SOL_POOL,
+        Some("token") => TOKEN_POOL,
+        _ => bail!(USAGE),
+    };
+
+    println!("{:>12}  {:>12}  {:>20}", "slot", "timestamp", "total_staked");
+
+    for point in store.tvl_history(pool)? {
+        println!("{:>12}  {:>12}  {:>20}", point.slot, point.timestamp, point.total_staked);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,563 @@
+// SQLite state rebuilt from events.
+//
+// Every event is recorded once in `events`, keyed by (signature, index), and only
+// applied to the derived tables the first time it is seen - re-ingesting the same
+// dump is a no-op.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, Transaction};
+use staking_token_contract::ConfigChange;
+
+use crate::events::{IndexedEvent, SolEvent, TokenEvent};
+use crate::logs::RawTransaction;
+
+pub const SOL_POOL: &str = "sol";
+pub const TOKEN_POOL: &str = "token";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS events (
+    signature TEXT NOT NULL,
+    event_index INTEGER NOT NULL,
+    tx_slot INTEGER,
+    block_time INTEGER,
+    program TEXT NOT NULL,
+    name TEXT NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+
+CREATE TABLE IF NOT EXISTS pools (
+    pool TEXT PRIMARY KEY,
+    total_staked INTEGER NOT NULL DEFAULT 0,
+    current_season INTEGER NOT NULL DEFAULT 0,
+    token_mint TEXT,
+    reward_rate INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS tvl_history (
+    pool TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    delta INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS sol_stakers (
+    user TEXT PRIMARY KEY,
+    staked_amount INTEGER NOT NULL DEFAULT 0,
+    total_points INTEGER NOT NULL DEFAULT 0,
+    claimed_points INTEGER NOT NULL DEFAULT 0,
+    season INTEGER NOT NULL DEFAULT 0,
+    stake_start_time INTEGER NOT NULL DEFAULT 0,
+    last_update INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS season_points (
+    user TEXT NOT NULL,
+    season INTEGER NOT NULL,
+    points INTEGER NOT NULL,
+    PRIMARY KEY (user, season)
+);
+
+CREATE TABLE IF NOT EXISTS token_stakers (
+    user TEXT PRIMARY KEY,
+    staked_amount INTEGER NOT NULL DEFAULT 0,
+    rewards_claimed INTEGER NOT NULL DEFAULT 0,
+    last_slot INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS airdrops (
+    distributor TEXT PRIMARY KEY,
+    season INTEGER NOT NULL,
+    max_total_claim INTEGER NOT NULL,
+    clawback_slot INTEGER NOT NULL,
+    clawed_back INTEGER NOT NULL DEFAULT 0,
+    clawed_back_amount INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS airdrop_claims (
+    distributor TEXT NOT NULL,
+    season INTEGER NOT NULL,
+    leaf_index INTEGER NOT NULL,
+    claimant TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    PRIMARY KEY (distributor, leaf_index)
+);
+";
+
+pub struct Store {
+    conn: Connection,
+}
+
+pub struct LeaderboardRow {
+    pub user: String,
+    pub staked_amount: u64,
+    pub score: u64,
+}
+
+pub struct TvlPoint {
+    pub slot: u64,
+    pub timestamp: i64,
+    pub total_staked: u64,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Store { conn })
+    }
+
+    // Returns how many new events were applied
+    pub fn ingest(&mut self, tx: &RawTransaction, events: &[(Vec<u8>, IndexedEvent)]) -> Result<usize> {
+        let db = self.conn.transaction()?;
+        let mut applied = 0;
+
+        for (index, (data, event)) in events.iter().enumerate() {
+            let inserted = db.execute(
+                "INSERT OR IGNORE INTO events (signature, event_index, tx_slot, block_time, program, name, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    tx.signature,
+                    index as i64,
+                    tx.slot.map(|slot| slot as i64),
+                    tx.block_time,
+                    event.program_id().to_string(),
+                    event.name(),
+                    data,
+                ],
+            )?;
+
+            if inserted == 0 {
+                continue;
+            }
+
+            match event {
+                IndexedEvent::Sol(event) => apply_sol_event(&db, event)?,
+                IndexedEvent::Token(event) => apply_token_event(&db, event)?,
+            }
+
+            applied += 1;
+        }
+
+        db.commit()?;
+        Ok(applied)
+    }
+
+    pub fn sol_leaderboard(&self, limit: u32) -> Result<Vec<LeaderboardRow>> {
+        self.leaderboard(
+            "SELECT user, staked_amount, total_points + claimed_points FROM sol_stakers
+             ORDER BY total_points + claimed_points DESC LIMIT ?1",
+            limit,
+        )
+    }
+
+    pub fn season_leaderboard(&self, season: u64, limit: u32) -> Result<Vec<LeaderboardRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT sp.user, COALESCE(s.staked_amount, 0), sp.points FROM season_points sp
+             LEFT JOIN sol_stakers s ON s.user = sp.user
+             WHERE sp.season = ?1 ORDER BY sp.points DESC LIMIT ?2",
+        )?;
+
+        let rows = stmt
+            .query_map(params![season as i64, limit], leaderboard_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(rows)
+    }
+
+    pub fn token_leaderboard(&self, limit: u32) -> Result<Vec<LeaderboardRow>> {
+        self.leaderboard(
+            "SELECT user, staked_amount, rewards_claimed FROM token_stakers
+             ORDER BY staked_amount DESC LIMIT ?1",
+            limit,
+        )
+    }
+
+    fn leaderboard(&self, sql: &str, limit: u32) -> Result<Vec<LeaderboardRow>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt
+            .query_map(params![limit], leaderboard_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(rows)
+    }
+
+    // One point per slot, summing the deltas in slot order so backfilled or out of order
+    // dumps give the same history. Transactions within a slot can't be ordered from the
+    // dumps, so only the TVL at the end of each slot is reported.
+    pub fn tvl_history(&self, pool: &str) -> Result<Vec<TvlPoint>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT slot, MAX(timestamp), SUM(SUM(delta)) OVER (ORDER BY slot) FROM tvl_history
+             WHERE pool = ?1 GROUP BY slot ORDER BY slot",
+        )?;
+
+        let rows = stmt
+            .query_map(params![pool], |row| {
+                Ok(TvlPoint {
+                    slot: row.get::<_, i64>(0)? as u64,
+                    timestamp: row.get(1)?,
+                    total_staked: row.get::<_, i64>(2)? as u64,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        Ok(rows)
+    }
+}
+
+fn leaderboard_row(row: &rusqlite::Row) -> rusqlite::Result<LeaderboardRow> {
+    Ok(LeaderboardRow {
+        user: row.get(0)?,
+        staked_amount: row.get::<_, i64>(1)? as u64,
+        score: row.get::<_, i64>(2)? as u64,
+    })
+}
+
+// Moves the pool TVL by `delta` and records it for the history. Deltas are summed, not
+// clamped, so the total doesn't depend on the order events are ingested in
+fn update_tvl(db: &Transaction, pool: &str, delta: i128, slot: u64, timestamp: i64) -> Result<()> {
+    let delta = delta as i64;
+
+    db.execute(
+        "INSERT INTO pools (pool, total_staked) VALUES (?1, ?2)
+         ON CONFLICT(pool) DO UPDATE SET total_staked = total_staked + excluded.total_staked",
+        params![pool, delta],
+    )?;
+    db.execute(
+        "INSERT INTO tvl_history (pool, slot, timestamp, delta) VALUES (?1, ?2, ?3, ?4)",
+        params![pool, slot as i64, timestamp, delta],
+    )?;
+
+    Ok(())
+}
+
+fn apply_sol_event(db: &Transaction, event: &SolEvent) -> Result<()> {
+    match event {
+        SolEvent::ConfigInitialized(ev) => {
+            db.execute(
+                "INSERT INTO pools (pool, current_season) VALUES (?1, ?2)
+                 ON CONFLICT(pool) DO UPDATE SET current_season = excluded.current_season",
+                params![SOL_POOL, ev.season as i64],
+            )?;
+        }
+        SolEvent::SeasonEnded(ev) => {
+            db.execute(
+                "INSERT INTO pools (pool, current_season) VALUES (?1, ?2)
+                 ON CONFLICT(pool) DO UPDATE SET current_season = excluded.current_season",
+                params![SOL_POOL, ev.new_season as i64],
+            )?;
+        }
+        SolEvent::StakeAccountInitialized(ev) => {
+            db.execute(
+                "INSERT OR IGNORE INTO sol_stakers (user, season, last_update) VALUES (?1, ?2, ?3)",
+                params![ev.user.to_string(), ev.season as i64, ev.timestamp],
+            )?;
+        }
+        SolEvent::Staked(ev) => {
+            db.execute(
+                "INSERT INTO sol_stakers (user, staked_amount, total_points, stake_start_time, last_update)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = excluded.staked_amount,
+                    total_points = excluded.total_points, stake_start_time = excluded.stake_start_time,
+                    last_update = excluded.last_update",
+                params![
+                    ev.user.to_string(),
+                    ev.staked_amount as i64,
+                    ev.total_points as i64,
+                    ev.stake_start_time,
+                    ev.timestamp,
+                ],
+            )?;
+            update_tvl(db, SOL_POOL, ev.amount as i128, ev.slot, ev.timestamp)?;
+        }
+        SolEvent::Unstaked(ev) => {
+            db.execute(
+                "INSERT INTO sol_stakers (user, staked_amount, total_points, last_update)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = excluded.staked_amount,
+                    total_points = excluded.total_points, last_update = excluded.last_update,
+                    stake_start_time = CASE WHEN excluded.staked_amount = 0 THEN 0 ELSE stake_start_time END",
+                params![ev.user.to_string(), ev.staked_amount as i64, ev.total_points as i64, ev.timestamp],
+            )?;
+            update_tvl(db, SOL_POOL, -(ev.amount as i128), ev.slot, ev.timestamp)?;
+        }
+        SolEvent::PointsClaimed(ev) => {
+            db.execute(
+                "INSERT INTO sol_stakers (user, claimed_points, last_update) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user) DO UPDATE SET total_points = 0,
+                    claimed_points = claimed_points + excluded.claimed_points,
+                    last_update = excluded.last_update",
+                params![ev.user.to_string(), ev.points as i64, ev.timestamp],
+            )?;
+        }
+        SolEvent::PointsUpdated(ev) => {
+            db.execute(
+                "INSERT INTO sol_stakers (user, total_points, season, last_update) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(user) DO UPDATE SET total_points = excluded.total_points,
+                    season = excluded.season, last_update = excluded.last_update",
+                params![ev.user.to_string(), ev.total_points as i64, ev.season as i64, ev.timestamp],
+            )?;
+        }
+        SolEvent::SeasonRolled(ev) => {
+            db.execute(
+                "INSERT INTO season_points (user, season, points) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user, season) DO UPDATE SET points = excluded.points",
+                params![ev.user.to_string(), ev.season as i64, ev.season_total_points as i64],
+            )?;
+            db.execute(
+                "INSERT INTO sol_stakers (user, season) VALUES (?1, ?2)
+                 ON CONFLICT(user) DO UPDATE SET total_points = 0, season = excluded.season",
+                params![ev.user.to_string(), ev.new_season as i64],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_token_event(db: &Transaction, event: &TokenEvent) -> Result<()> {
+    match event {
+        TokenEvent::Initialized(ev) => {
+            db.execute(
+                "INSERT INTO pools (pool, token_mint, reward_rate) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(pool) DO UPDATE SET token_mint = excluded.token_mint,
+                    reward_rate = excluded.reward_rate",
+                params![
+                    TOKEN_POOL,
+                    ev.token_mint.to_string(),
+                    ev.reward_rate_per_token_per_slot as i64,
+                ],
+            )?;
+        }
+        TokenEvent::Staked(ev) => {
+            db.execute(
+                "INSERT INTO token_stakers (user, staked_amount, rewards_claimed, last_slot)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = excluded.staked_amount,
+                    rewards_claimed = rewards_claimed + excluded.rewards_claimed,
+                    last_slot = excluded.last_slot",
+                params![ev.user.to_string(), ev.staked_amount as i64, ev.rewards_paid as i64, ev.slot as i64],
+            )?;
+            update_tvl(db, TOKEN_POOL, ev.amount as i128, ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::StakedFor(ev) => {
+            // the position belongs to the beneficiary, the payer isn't tracked
+            db.execute(
+                "INSERT INTO token_stakers (user, staked_amount, last_slot) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = excluded.staked_amount,
+                    last_slot = excluded.last_slot",
+                params![ev.beneficiary.to_string(), ev.staked_amount as i64, ev.slot as i64],
+            )?;
+            update_tvl(db, TOKEN_POOL, ev.amount as i128, ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::GrantStaked(ev) => {
+            // grants are separate locked positions, they only count towards TVL
+            update_tvl(db, TOKEN_POOL, ev.amount as i128, ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::GrantClaimed(ev) => {
+            update_tvl(db, TOKEN_POOL, -(ev.amount as i128), ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::Unstaked(ev) => {
+            db.execute(
+                "INSERT INTO token_stakers (user, rewards_claimed, last_slot) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = 0,
+                    rewards_claimed = rewards_claimed + excluded.rewards_claimed,
+                    last_slot = excluded.last_slot",
+                params![ev.user.to_string(), ev.rewards_paid as i64, ev.slot as i64],
+            )?;
+            update_tvl(db, TOKEN_POOL, -(ev.amount as i128), ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::PositionTransferred(ev) => {
+            // receipts moved, the staked balance moves with them (TVL is unchanged)
+            db.execute(
+                "UPDATE token_stakers SET staked_amount = staked_amount - ?2, last_slot = ?3 WHERE user = ?1",
+                params![ev.from.to_string(), ev.amount as i64, ev.slot as i64],
+            )?;
+            db.execute(
+                "INSERT INTO token_stakers (user, staked_amount, last_slot) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = staked_amount + excluded.staked_amount,
+                    last_slot = excluded.last_slot",
+                params![ev.to.to_string(), ev.amount as i64, ev.slot as i64],
+            )?;
+        }
+        TokenEvent::Slashed(ev) => {
+            // seized tokens leave the vault
+            db.execute(
+                "UPDATE token_stakers SET staked_amount = ?2, last_slot = ?3 WHERE user = ?1",
+                params![ev.user.to_string(), ev.staked_amount as i64, ev.slot as i64],
+            )?;
+            update_tvl(db, TOKEN_POOL, -(ev.amount as i128), ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::RewardsClaimed(ev) => {
+            db.execute(
+                "INSERT INTO token_stakers (user, staked_amount, rewards_claimed, last_slot)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(user) DO UPDATE SET staked_amount = excluded.staked_amount,
+                    rewards_claimed = rewards_claimed + excluded.rewards_claimed,
+                    last_slot = excluded.last_slot",
+                params![ev.user.to_string(), ev.staked_amount as i64, ev.amount as i64, ev.slot as i64],
+            )?;
+        }
+        TokenEvent::RewardsCompounded(ev) => {
+            // the reward was minted into the vault, so it counts towards TVL
+            db.execute(
+                "UPDATE token_stakers SET staked_amount = ?2, rewards_claimed = rewards_claimed + ?3,
+                    last_slot = ?4 WHERE user = ?1",
+                params![ev.user.to_string(), ev.staked_amount as i64, ev.amount as i64, ev.slot as i64],
+            )?;
+            update_tvl(db, TOKEN_POOL, ev.amount as i128, ev.slot, ev.timestamp)?;
+        }
+        TokenEvent::AirdropClaimed(ev) => {
+            db.execute(
+                "INSERT OR IGNORE INTO airdrop_claims (distributor, season, leaf_index, claimant, amount, slot)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    ev.distributor.to_string(),
+                    ev.season as i64,
+                    ev.index as i64,
+                    ev.claimant.to_string(),
+                    ev.amount as i64,
+                    ev.slot as i64,
+                ],
+            )?;
+        }
+        TokenEvent::DistributorCreated(ev) => {
+            db.execute(
+                "INSERT OR IGNORE INTO airdrops (distributor, season, max_total_claim, clawback_slot)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    ev.distributor.to_string(),
+                    ev.season as i64,
+                    ev.max_total_claim as i64,
+                    ev.clawback_slot as i64,
+                ],
+            )?;
+        }
+        TokenEvent::AirdropClawedBack(ev) => {
+            db.execute(
+                "UPDATE airdrops SET clawed_back = 1, clawed_back_amount = ?2 WHERE distributor = ?1",
+                params![ev.distributor.to_string(), ev.amount as i64],
+            )?;
+        }
+        TokenEvent::ConfigChangeExecuted(ev) => {
+            if let ConfigChange::RewardRate { reward_rate } = ev.change {
+                db.execute(
+                    "UPDATE pools SET reward_rate = ?2 WHERE pool = ?1",
+                    params![TOKEN_POOL, reward_rate as i64],
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::prelude::Pubkey;
+
+    fn tx(signature: &str) -> RawTransaction {
+        RawTransaction {
+            signature: signature.to_string(),
+            slot: Some(1),
+            block_time: None,
+            failed: false,
+            logs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn flags_a_clawback_even_when_nothing_was_left() {
+        let mut store = Store::open(":memory:").unwrap();
+        let distributor = Pubkey::new_unique();
+
+        let created = TokenEvent::DistributorCreated(staking_token_contract::DistributorCreated {
+            distributor,
+            season: 1,
+            root: [0; 32],
+            max_total_claim: 100,
+            max_num_nodes: 1,
+            clawback_slot: 10,
+            slot: 1,
+            timestamp: 1,
+        });
+        let clawed_back =
+            TokenEvent::AirdropClawedBack(staking_token_contract::AirdropClawedBack {
+                distributor,
+                season: 1,
+                amount: 0,
+                destination: Pubkey::new_unique(),
+                slot: 11,
+                timestamp: 2,
+            });
+
+        let events = vec![
+            (vec![0], IndexedEvent::Token(created)),
+            (vec![1], IndexedEvent::Token(clawed_back)),
+        ];
+        assert_eq!(store.ingest(&tx("sig"), &events).unwrap(), 2);
+
+        let (flag, amount): (i64, Option<i64>) = store
+            .conn
+            .query_row(
+                "SELECT clawed_back, clawed_back_amount FROM airdrops WHERE distributor = ?1",
+                params![distributor.to_string()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+
+        assert_eq!((flag, amount), (1, Some(0)));
+    }
+
+    fn sol_staked(amount: u64, slot: u64) -> IndexedEvent {
+        IndexedEvent::Sol(SolEvent::Staked(staking_contract::Staked {
+            user: Pubkey::new_unique(),
+            amount,
+            staked_amount: amount,
+            total_points: 0,
+            stake_start_time: 0,
+            slot,
+            timestamp: slot as i64,
+        }))
+    }
+
+    fn sol_unstaked(amount: u64, slot: u64) -> IndexedEvent {
+        IndexedEvent::Sol(SolEvent::Unstaked(staking_contract::Unstaked {
+            user: Pubkey::new_unique(),
+            amount,
+            staked_amount: 0,
+            total_points: 0,
+            slot,
+            timestamp: slot as i64,
+        }))
+    }
+
+    #[test]
+    fn builds_the_tvl_history_in_slot_order() {
+        let mut store = Store::open(":memory:").unwrap();
+
+        // a backfill ingests the later transactions first
+        store
+            .ingest(&tx("c"), &[(vec![0], sol_unstaked(30, 30))])
+            .unwrap();
+        store
+            .ingest(
+                &tx("b"),
+                &[(vec![0], sol_staked(20, 20)), (vec![1], sol_staked(5, 20))],
+            )
+            .unwrap();
+        store
+            .ingest(&tx("a"), &[(vec![0], sol_staked(100, 10))])
+            .unwrap();
+
+        let history: Vec<_> = store
+            .tvl_history(SOL_POOL)
+            .unwrap()
+            .iter()
+            .map(|point| (point.slot, point.total_staked))
+            .collect();
+
+        assert_eq!(history, vec![(10, 100), (20, 125), (30, 95)]);
+    }
+}
@@ -1,4 +1,4 @@
-use anchor_lang::{prelude::*, solana_program::system_instruction::SystemError};
+use anchor_lang::prelude::*;
 
 declare_id!("HXShNFBMdtc268HDoevdAwxwqHDVJdyiAmBZ2yTUFNVB");
 
@@ -10,6 +10,50 @@ pub mod Counter{
     use super::*;
 
     pub fn initialize(context:Context<CreatePdaAccount>)->Result<()>{
+        let pda_account = &mut context.accounts.pda_account;
+
+        pda_account.owner = context.accounts.signer.key();
+        pda_account.count = 0;
+        pda_account.bump = context.bumps.pda_account;
+
+        emit_counter_updated(pda_account)
+    }
+
+    pub fn increment(context:Context<UpdateCounter>)->Result<()>{
+        let pda_account = &mut context.accounts.pda_account;
+
+        pda_account.count = pda_account.count.checked_add(1).ok_or(PdaError::Overflow)?;
+
+        emit_counter_updated(pda_account)
+    }
+
+    pub fn decrement(context:Context<UpdateCounter>)->Result<()>{
+        let pda_account = &mut context.accounts.pda_account;
+
+        pda_account.count = pda_account.count.checked_sub(1).ok_or(PdaError::Underflow)?;
+
+        emit_counter_updated(pda_account)
+    }
+
+    pub fn reset(context:Context<UpdateCounter>)->Result<()>{
+        let pda_account = &mut context.accounts.pda_account;
+
+        pda_account.count = 0;
+
+        emit_counter_updated(pda_account)
+    }
+
+    // Closes the counter and refunds the rent to the owner
+    pub fn close(context:Context<CloseCounter>)->Result<()>{
+        let clock = Clock::get()?;
+
+        emit!(CounterClosed{
+            owner: context.accounts.signer.key(),
+            count: context.accounts.pda_account.count,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
@@ -22,35 +66,406 @@ pub mod Counter{
 
 
         let cpi_context = CpiContext::new(pgm_id,Transfer{
-            from:from_pubKey,
-            to:to_pubKey
+            from:from_pubKey.clone(),
+            to:to_pubKey.clone()
         });
 
         transfer(cpi_context, amount)?;
 
+        let clock = Clock::get()?;
+
+        emit!(Transferred{
+            from: from_pubKey.key(),
+            to: to_pubKey.key(),
+            amount,
+            pda_signed: false,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
-    pub fn transfer_with_pda_signing(context:Context<TransferAmountWithoutPDASigining>,amount:u64)->Result<()>{
-        
+    // SOL wallet owned by the program - the lamports sit in a system-owned PDA
+    // that only this program can sign for
+    pub fn create_vault(context:Context<CreateVault>,max_withdrawal:u64)->Result<()>{
+        require!(max_withdrawal>0, PdaError::InvalidAmount);
+
+        let vault_info = &mut context.accounts.vault_info;
+
+        vault_info.owner = context.accounts.signer.key();
+        vault_info.max_withdrawal = max_withdrawal;
+        vault_info.vault_bump = context.bumps.vault;
+        vault_info.bump = context.bumps.vault_info;
+
+        let clock = Clock::get()?;
+
+        emit!(VaultCreated{
+            owner: vault_info.owner,
+            vault: context.accounts.vault.key(),
+            max_withdrawal,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Anyone can top up a vault, no PDA signature needed
+    pub fn deposit(context:Context<DepositToVault>,amount:u64)->Result<()>{
+        require!(amount>0, PdaError::InvalidAmount);
+
         let pgm_id = context.accounts.system_program.to_account_info();
-        let from_pubKey = context.accounts.Signer.to_account_info();
-        let to_pubKey = context.accounts.User_acc.to_account_info();
-        let bump = context.bumps;
-            // Extract bump for PDA
-    let bump = *context.bumps.get("pda_account").unwrap();
+        let from = context.accounts.signer.to_account_info();
+        let to = context.accounts.vault.to_account_info();
+
+        let cpi_context = CpiContext::new(pgm_id,Transfer{
+            from:from.clone(),
+            to:to.clone()
+        });
 
-        let seeds = &[b"client", from_pubKey.key.as_ref(), &[bump]];
+        transfer(cpi_context, amount)?;
 
-        let CpiContext = CpiContext::new(pgm_id,Transfer{
-            from:from_pubKey,
-            to:to_pubKey
-        }).with_signer(&[seeds]);
+        let clock = Clock::get()?;
 
-        transfer(CpiContext, amount)?;
-        
-        Ok(());
+        emit!(Transferred{
+            from: from.key(),
+            to: to.key(),
+            amount,
+            pda_signed: false,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
     }
+
+    // Moves SOL out of the vault, the program signs for the vault PDA
+    pub fn transfer_with_pda_signing(context:Context<TransferAmountWithPDASigning>,amount:u64)->Result<()>{
+        require!(amount>0, PdaError::InvalidAmount);
+
+        let vault_info = &context.accounts.vault_info;
+
+        require!(amount<=vault_info.max_withdrawal, PdaError::WithdrawalLimitExceeded);
+
+        transfer_from_vault(
+            &context.accounts.system_program,
+            &context.accounts.vault,
+            &context.accounts.recipient,
+            vault_info,
+            amount
+        )
+    }
+
+    pub fn set_withdrawal_limit(context:Context<UpdateVault>,max_withdrawal:u64)->Result<()>{
+        require!(max_withdrawal>0, PdaError::InvalidAmount);
+
+        let vault_info = &mut context.accounts.vault_info;
+
+        vault_info.max_withdrawal = max_withdrawal;
+
+        msg!("Vault withdrawal limit set to {} lamports", max_withdrawal);
+
+        Ok(())
+    }
+
+    // Lets `delegate` spend from the vault: up to `max_total` overall, `period_cap` per
+    // `period_seconds` window, until `expires_at` (unix timestamp)
+    pub fn grant_allowance(
+        context:Context<GrantAllowance>,
+        delegate:Pubkey,
+        max_total:u64,
+        period_seconds:i64,
+        period_cap:u64,
+        expires_at:i64
+    )->Result<()>{
+        require!(max_total>0 && period_cap>0, PdaError::InvalidAmount);
+        require!(period_seconds>0, PdaError::InvalidPeriod);
+
+        let clock = Clock::get()?;
+        require!(expires_at>clock.unix_timestamp, PdaError::AllowanceExpired);
+
+        let allowance = &mut context.accounts.allowance;
+
+        allowance.vault_info = context.accounts.vault_info.key();
+        allowance.delegate = delegate;
+        allowance.max_total = max_total;
+        allowance.spent = 0;
+        allowance.period_seconds = period_seconds;
+        allowance.period_cap = period_cap;
+        allowance.period_start = clock.unix_timestamp;
+        allowance.period_spent = 0;
+        allowance.expires_at = expires_at;
+        allowance.bump = context.bumps.allowance;
+
+        emit!(AllowanceGranted{
+            owner: context.accounts.signer.key(),
+            delegate,
+            max_total,
+            period_seconds,
+            period_cap,
+            expires_at,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Closes the allowance, rent goes back to the owner
+    pub fn revoke_allowance(context:Context<RevokeAllowance>)->Result<()>{
+        let clock = Clock::get()?;
+
+        emit!(AllowanceRevoked{
+            owner: context.accounts.signer.key(),
+            delegate: context.accounts.allowance.delegate,
+            spent: context.accounts.allowance.spent,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Delegate spends from the owner's vault within its allowance
+    pub fn delegate_withdraw(context:Context<DelegateWithdraw>,amount:u64)->Result<()>{
+        require!(amount>0, PdaError::InvalidAmount);
+
+        let vault_info = &context.accounts.vault_info;
+        let allowance = &mut context.accounts.allowance;
+        let clock = Clock::get()?;
+
+        require!(clock.unix_timestamp<allowance.expires_at, PdaError::AllowanceExpired);
+        require!(amount<=vault_info.max_withdrawal, PdaError::WithdrawalLimitExceeded);
+
+        // roll over to the window the current time falls in
+        let since_period_start = clock.unix_timestamp.checked_sub(allowance.period_start).ok_or(PdaError::Underflow)?;
+        if since_period_start>=allowance.period_seconds {
+            allowance.period_start = clock.unix_timestamp - since_period_start % allowance.period_seconds;
+            allowance.period_spent = 0;
+        }
+
+        allowance.spent = allowance.spent.checked_add(amount).ok_or(PdaError::Overflow)?;
+        require!(allowance.spent<=allowance.max_total, PdaError::AllowanceExceeded);
+
+        allowance.period_spent = allowance.period_spent.checked_add(amount).ok_or(PdaError::Overflow)?;
+        require!(allowance.period_spent<=allowance.period_cap, PdaError::PeriodCapExceeded);
+
+        transfer_from_vault(
+            &context.accounts.system_program,
+            &context.accounts.vault,
+            &context.accounts.recipient,
+            vault_info,
+            amount
+        )
+    }
+
+    // Locks rate_per_second * (end_time - start_time) lamports in an escrow PDA that
+    // vests linearly to the recipient. The escrow also keeps its rent-exempt reserve,
+    // which goes back to the owner when the stream is cancelled.
+    pub fn create_stream(
+        context:Context<CreateStream>,
+        stream_id:u64,
+        recipient:Pubkey,
+        rate_per_second:u64,
+        start_time:i64,
+        end_time:i64
+    )->Result<()>{
+        require!(rate_per_second>0, PdaError::InvalidAmount);
+        require!(end_time>start_time, PdaError::InvalidPeriod);
+
+        let clock = Clock::get()?;
+        require!(end_time>clock.unix_timestamp, PdaError::InvalidPeriod);
+
+        let deposited = rate_per_second
+            .checked_mul((end_time - start_time) as u64)
+            .ok_or(PdaError::Overflow)?;
+
+        let stream = &mut context.accounts.stream;
+
+        stream.owner = context.accounts.signer.key();
+        stream.recipient = recipient;
+        stream.stream_id = stream_id;
+        stream.rate_per_second = rate_per_second;
+        stream.start_time = start_time;
+        stream.end_time = end_time;
+        stream.deposited = deposited;
+        stream.withdrawn = 0;
+        stream.escrow_bump = context.bumps.escrow;
+        stream.bump = context.bumps.stream;
+
+        let rent_reserve = Rent::get()?.minimum_balance(0);
+        let amount = deposited.checked_add(rent_reserve).ok_or(PdaError::Overflow)?;
+
+        let pgm_id = context.accounts.system_program.to_account_info();
+        let from = context.accounts.signer.to_account_info();
+        let to = context.accounts.escrow.to_account_info();
+
+        let cpi_context = CpiContext::new(pgm_id,Transfer{
+            from:from.clone(),
+            to:to.clone()
+        });
+
+        transfer(cpi_context, amount)?;
+
+        emit!(StreamCreated{
+            stream: stream.key(),
+            owner: stream.owner,
+            recipient,
+            rate_per_second,
+            start_time,
+            end_time,
+            deposited,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Recipient pulls whatever has vested so far
+    pub fn withdraw_streamed(context:Context<WithdrawStreamed>)->Result<()>{
+        let clock = Clock::get()?;
+        let stream = &mut context.accounts.stream;
+
+        let available = vested_amount(stream, clock.unix_timestamp)?
+            .checked_sub(stream.withdrawn)
+            .ok_or(PdaError::Underflow)?;
+
+        require!(available>0, PdaError::NothingVested);
+
+        stream.withdrawn = stream.withdrawn.checked_add(available).ok_or(PdaError::Overflow)?;
+
+        let stream_key = stream.key();
+        let seeds: &[&[u8]] = &[b"stream-escrow", stream_key.as_ref(), &[stream.escrow_bump]];
+
+        transfer_signed(
+            &context.accounts.system_program,
+            &context.accounts.escrow.to_account_info(),
+            &context.accounts.recipient.to_account_info(),
+            seeds,
+            available
+        )
+    }
+
+    // Owner stops the stream: the recipient gets what already vested,
+    // the owner gets back the unvested remainder and the escrow rent
+    pub fn cancel_stream(context:Context<CancelStream>)->Result<()>{
+        let clock = Clock::get()?;
+        let stream = &context.accounts.stream;
+
+        let owed = vested_amount(stream, clock.unix_timestamp)?
+            .checked_sub(stream.withdrawn)
+            .ok_or(PdaError::Underflow)?;
+
+        let stream_key = stream.key();
+        let seeds: &[&[u8]] = &[b"stream-escrow", stream_key.as_ref(), &[stream.escrow_bump]];
+        let escrow = context.accounts.escrow.to_account_info();
+
+        if owed>0 {
+            transfer_signed(
+                &context.accounts.system_program,
+                &escrow,
+                &context.accounts.recipient.to_account_info(),
+                seeds,
+                owed
+            )?;
+        }
+
+        let refund = escrow.lamports();
+
+        if refund>0 {
+            transfer_signed(
+                &context.accounts.system_program,
+                &escrow,
+                &context.accounts.signer.to_account_info(),
+                seeds,
+                refund
+            )?;
+        }
+
+        emit!(StreamCancelled{
+            stream: stream_key,
+            owner: stream.owner,
+            recipient: stream.recipient,
+            paid_to_recipient: owed,
+            refunded: refund,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+}
+
+// Linear vesting between start_time and end_time
+fn vested_amount(stream:&PaymentStream, now:i64)->Result<u64>{
+    if now<=stream.start_time {
+        return Ok(0);
+    }
+
+    let elapsed = (now.min(stream.end_time) - stream.start_time) as u64;
+
+    let vested = stream.rate_per_second.checked_mul(elapsed).ok_or(PdaError::Overflow)?;
+
+    Ok(vested)
+}
+
+// System transfer out of the vault PDA, signed with the bump stored at create_vault
+fn transfer_from_vault<'info>(
+    system_program:&Program<'info,System>,
+    vault:&SystemAccount<'info>,
+    recipient:&UncheckedAccount<'info>,
+    vault_info:&VaultInfo,
+    amount:u64
+)->Result<()>{
+    let seeds: &[&[u8]] = &[b"client-vault", vault_info.owner.as_ref(), &[vault_info.vault_bump]];
+
+    transfer_signed(system_program, &vault.to_account_info(), &recipient.to_account_info(), seeds, amount)
+}
+
+// System transfer out of a system-owned PDA of this program
+fn transfer_signed<'info>(
+    system_program:&Program<'info,System>,
+    from:&AccountInfo<'info>,
+    to:&AccountInfo<'info>,
+    seeds:&[&[u8]],
+    amount:u64
+)->Result<()>{
+    let signer_seeds = &[seeds];
+
+    let cpi_context = CpiContext::new(system_program.to_account_info(),anchor_lang::system_program::Transfer{
+        from:from.clone(),
+        to:to.clone()
+    }).with_signer(signer_seeds);
+
+    anchor_lang::system_program::transfer(cpi_context, amount)?;
+
+    let clock = Clock::get()?;
+
+    emit!(Transferred{
+        from: from.key(),
+        to: to.key(),
+        amount,
+        pda_signed: true,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+fn emit_counter_updated(pda_account:&PdaAccount)->Result<()>{
+    let clock = Clock::get()?;
+
+    emit!(CounterUpdated{
+        owner: pda_account.owner,
+        count: pda_account.count,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
 }
 
 // PDA Creation
@@ -74,6 +489,39 @@ pub struct CreatePdaAccount<'info>{
     pub system_program: Program<'info,System>,
 }
 
+// Only the owner can touch their counter
+#[derive(Accounts)]
+pub struct UpdateCounter<'info>{
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"client",
+            signer.key().as_ref()
+        ],
+        bump = pda_account.bump,
+        constraint = pda_account.owner == signer.key() @PdaError::Unauthorized
+    )]
+    pub pda_account : Account<'info,PdaAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCounter<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"client",
+            signer.key().as_ref()
+        ],
+        bump = pda_account.bump,
+        constraint = pda_account.owner == signer.key() @PdaError::Unauthorized,
+        close = signer
+    )]
+    pub pda_account : Account<'info,PdaAccount>,
+}
+
 
 // Transferring without PDA signing 
 #[derive(Accounts)]
@@ -85,7 +533,439 @@ pub struct TransferAmountWithoutPDASigining<'info>{
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct CreateVault<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8+32+8+1+1,
+        seeds = [
+            b"vault-info",
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    // system-owned, holds the lamports. Only the bump is needed here
+    #[account(
+        seeds = [
+            b"client-vault",
+            signer.key().as_ref()
+        ],
+        bump
+    )]
+    pub vault : SystemAccount<'info>,
+    pub system_program: Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToVault<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [
+            b"vault-info",
+            vault_info.owner.as_ref()
+        ],
+        bump = vault_info.bump
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    #[account(
+        mut,
+        seeds = [
+            b"client-vault",
+            vault_info.owner.as_ref()
+        ],
+        bump = vault_info.vault_bump
+    )]
+    pub vault : SystemAccount<'info>,
+    pub system_program: Program<'info,System>,
+}
+
+// Transferring with PDA signing - only the vault owner can move funds out
+#[derive(Accounts)]
+pub struct TransferAmountWithPDASigning<'info>{
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [
+            b"vault-info",
+            signer.key().as_ref()
+        ],
+        bump = vault_info.bump,
+        constraint = vault_info.owner == signer.key() @PdaError::Unauthorized
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    #[account(
+        mut,
+        seeds = [
+            b"client-vault",
+            signer.key().as_ref()
+        ],
+        bump = vault_info.vault_bump
+    )]
+    pub vault : SystemAccount<'info>,
+    /// CHECK: any account can receive lamports
+    #[account(mut)]
+    pub recipient : UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVault<'info>{
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"vault-info",
+            signer.key().as_ref()
+        ],
+        bump = vault_info.bump,
+        constraint = vault_info.owner == signer.key() @PdaError::Unauthorized
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+}
+
 #[account]
 pub struct PdaAccount{
-    pub count:u64
+    pub owner:Pubkey,
+    pub count:u64,
+    pub bump:u8,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate:Pubkey)]
+pub struct GrantAllowance<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [
+            b"vault-info",
+            signer.key().as_ref()
+        ],
+        bump = vault_info.bump,
+        constraint = vault_info.owner == signer.key() @PdaError::Unauthorized
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8+32+32+8+8+8+8+8+8+8+1,
+        seeds = [
+            b"allowance",
+            vault_info.key().as_ref(),
+            delegate.as_ref()
+        ],
+        bump
+    )]
+    pub allowance : Account<'info,Allowance>,
+    pub system_program: Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeAllowance<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [
+            b"vault-info",
+            signer.key().as_ref()
+        ],
+        bump = vault_info.bump,
+        constraint = vault_info.owner == signer.key() @PdaError::Unauthorized
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            vault_info.key().as_ref(),
+            allowance.delegate.as_ref()
+        ],
+        bump = allowance.bump,
+        close = signer
+    )]
+    pub allowance : Account<'info,Allowance>,
+}
+
+#[derive(Accounts)]
+pub struct DelegateWithdraw<'info>{
+    pub delegate: Signer<'info>,
+    #[account(
+        seeds = [
+            b"vault-info",
+            vault_info.owner.as_ref()
+        ],
+        bump = vault_info.bump
+    )]
+    pub vault_info : Account<'info,VaultInfo>,
+    #[account(
+        mut,
+        seeds = [
+            b"allowance",
+            vault_info.key().as_ref(),
+            delegate.key().as_ref()
+        ],
+        bump = allowance.bump,
+        constraint = allowance.delegate == delegate.key() @PdaError::Unauthorized
+    )]
+    pub allowance : Account<'info,Allowance>,
+    #[account(
+        mut,
+        seeds = [
+            b"client-vault",
+            vault_info.owner.as_ref()
+        ],
+        bump = vault_info.vault_bump
+    )]
+    pub vault : SystemAccount<'info>,
+    /// CHECK: any account can receive lamports
+    #[account(mut)]
+    pub recipient : UncheckedAccount<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(stream_id:u64, recipient:Pubkey)]
+pub struct CreateStream<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        init,
+        payer = signer,
+        space = 8+32+32+8+8+8+8+8+8+1+1,
+        seeds = [
+            b"stream",
+            signer.key().as_ref(),
+            recipient.as_ref(),
+            stream_id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub stream : Account<'info,PaymentStream>,
+    #[account(
+        mut,
+        seeds = [
+            b"stream-escrow",
+            stream.key().as_ref()
+        ],
+        bump
+    )]
+    pub escrow : SystemAccount<'info>,
+    pub system_program: Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawStreamed<'info>{
+    #[account(mut)]
+    pub recipient: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"stream",
+            stream.owner.as_ref(),
+            recipient.key().as_ref(),
+            stream.stream_id.to_le_bytes().as_ref()
+        ],
+        bump = stream.bump,
+        constraint = stream.recipient == recipient.key() @PdaError::Unauthorized
+    )]
+    pub stream : Account<'info,PaymentStream>,
+    #[account(
+        mut,
+        seeds = [
+            b"stream-escrow",
+            stream.key().as_ref()
+        ],
+        bump = stream.escrow_bump
+    )]
+    pub escrow : SystemAccount<'info>,
+    pub system_program: Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelStream<'info>{
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [
+            b"stream",
+            signer.key().as_ref(),
+            stream.recipient.as_ref(),
+            stream.stream_id.to_le_bytes().as_ref()
+        ],
+        bump = stream.bump,
+        constraint = stream.owner == signer.key() @PdaError::Unauthorized,
+        close = signer
+    )]
+    pub stream : Account<'info,PaymentStream>,
+    #[account(
+        mut,
+        seeds = [
+            b"stream-escrow",
+            stream.key().as_ref()
+        ],
+        bump = stream.escrow_bump
+    )]
+    pub escrow : SystemAccount<'info>,
+    /// CHECK: only receives lamports, checked against the stream
+    #[account(
+        mut,
+        address = stream.recipient @PdaError::Unauthorized
+    )]
+    pub recipient : UncheckedAccount<'info>,
+    pub system_program: Program<'info,System>,
+}
+
+#[account]
+pub struct VaultInfo{
+    pub owner:Pubkey,
+    pub max_withdrawal:u64, // per withdrawal, in lamports
+    pub vault_bump:u8,
+    pub bump:u8,
+}
+
+// Spending rights of a delegate over one vault
+#[account]
+pub struct Allowance{
+    pub vault_info:Pubkey,
+    pub delegate:Pubkey,
+    pub max_total:u64,
+    pub spent:u64,
+    pub period_seconds:i64,
+    pub period_cap:u64,
+    pub period_start:i64,
+    pub period_spent:u64,
+    pub expires_at:i64,
+    pub bump:u8,
+}
+
+// Lamports vesting linearly from owner to recipient
+#[account]
+pub struct PaymentStream{
+    pub owner:Pubkey,
+    pub recipient:Pubkey,
+    pub stream_id:u64,
+    pub rate_per_second:u64,
+    pub start_time:i64,
+    pub end_time:i64,
+    pub deposited:u64,
+    pub withdrawn:u64,
+    pub escrow_bump:u8,
+    pub bump:u8,
+}
+
+#[event]
+pub struct CounterUpdated{
+    pub owner:Pubkey,
+    pub count:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct CounterClosed{
+    pub owner:Pubkey,
+    pub count:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct VaultCreated{
+    pub owner:Pubkey,
+    pub vault:Pubkey,
+    pub max_withdrawal:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct AllowanceGranted{
+    pub owner:Pubkey,
+    pub delegate:Pubkey,
+    pub max_total:u64,
+    pub period_seconds:i64,
+    pub period_cap:u64,
+    pub expires_at:i64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct AllowanceRevoked{
+    pub owner:Pubkey,
+    pub delegate:Pubkey,
+    pub spent:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct StreamCreated{
+    pub stream:Pubkey,
+    pub owner:Pubkey,
+    pub recipient:Pubkey,
+    pub rate_per_second:u64,
+    pub start_time:i64,
+    pub end_time:i64,
+    pub deposited:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct StreamCancelled{
+    pub stream:Pubkey,
+    pub owner:Pubkey,
+    pub recipient:Pubkey,
+    pub paid_to_recipient:u64,
+    pub refunded:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct Transferred{
+    pub from:Pubkey,
+    pub to:Pubkey,
+    pub amount:u64,
+    pub pda_signed:bool,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[error_code]
+pub enum PdaError{
+    #[msg("Counter overflow")]
+    Overflow,
+
+    #[msg("Counter can't go below 0")]
+    Underflow,
+
+    #[msg("Only the owner can do this")]
+    Unauthorized,
+
+    #[msg("Amount must be greater than 0")]
+    InvalidAmount,
+
+    #[msg("Amount is above the vault's per withdrawal limit")]
+    WithdrawalLimitExceeded,
+
+    #[msg("Allowance period must be greater than 0")]
+    InvalidPeriod,
+
+    #[msg("Allowance has expired")]
+    AllowanceExpired,
+
+    #[msg("Allowance total exceeded")]
+    AllowanceExceeded,
+
+    #[msg("Allowance per period cap exceeded")]
+    PeriodCapExceeded,
+
+    #[msg("Nothing has vested yet")]
+    NothingVested,
 }
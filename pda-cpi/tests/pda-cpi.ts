@@ -1,16 +1,227 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { PdaCpi } from "../target/types/pda_cpi";
+import { assert } from "chai";
 
 describe("pda-cpi", () => {
   // Configure the client to use the local cluster.
   anchor.setProvider(anchor.AnchorProvider.env());
 
+  const provider = anchor.getProvider();
+
   const program = anchor.workspace.pdaCpi as Program<PdaCpi>;
 
+  const [counter_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("client"), provider.publicKey.toBuffer()],
+    program.programId
+  );
+
   it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+    await program.methods.initialize().rpc();
+
+    const counter = await program.account.pdaAccount.fetch(counter_pda);
+
+    assert.equal(counter.count.toNumber(), 0);
+    assert.isTrue(counter.owner.equals(provider.publicKey));
+  });
+
+  it("increments and decrements", async () => {
+    await program.methods.increment().rpc();
+    await program.methods.increment().rpc();
+    await program.methods.decrement().rpc();
+
+    const counter = await program.account.pdaAccount.fetch(counter_pda);
+
+    assert.equal(counter.count.toNumber(), 1);
+  });
+
+  it("can't go below zero", async () => {
+    await program.methods.reset().rpc();
+
+    try {
+      await program.methods.decrement().rpc();
+      assert.fail("decrement below zero should fail");
+    } catch (err) {
+      assert.include(err.toString(), "Underflow");
+    }
+  });
+
+  it("closes the counter", async () => {
+    await program.methods.close().rpc();
+
+    const account = await provider.connection.getAccountInfo(counter_pda);
+
+    assert.isNull(account);
+  });
+
+  describe("vault", () => {
+    const recipient = anchor.web3.Keypair.generate();
+    const maxWithdrawal = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 2);
+
+    const [vault_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("client-vault"), provider.publicKey.toBuffer()],
+      program.programId
+    );
+
+    const [vault_info_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("vault-info"), provider.publicKey.toBuffer()],
+      program.programId
+    );
+
+    it("creates a vault and takes deposits", async () => {
+      await program.methods.createVault(maxWithdrawal).rpc();
+
+      await program.methods
+        .deposit(new anchor.BN(anchor.web3.LAMPORTS_PER_SOL))
+        .accountsPartial({ vaultInfo: vault_info_pda, vault: vault_pda })
+        .rpc();
+
+      const balance = await provider.connection.getBalance(vault_pda);
+
+      assert.equal(balance, anchor.web3.LAMPORTS_PER_SOL);
+    });
+
+    it("withdraws with the PDA signature", async () => {
+      const amount = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 4);
+
+      await program.methods
+        .transferWithPdaSigning(amount)
+        .accounts({ recipient: recipient.publicKey })
+        .rpc();
+
+      const balance = await provider.connection.getBalance(
+        recipient.publicKey
+      );
+
+      assert.equal(balance, amount.toNumber());
+    });
+
+    it("rejects withdrawals above the limit", async () => {
+      try {
+        await program.methods
+          .transferWithPdaSigning(maxWithdrawal.addn(1))
+          .accounts({ recipient: recipient.publicKey })
+          .rpc();
+        assert.fail("withdrawal above the limit should fail");
+      } catch (err) {
+        assert.include(err.toString(), "WithdrawalLimitExceeded");
+      }
+    });
+
+    it("lets a delegate spend within its allowance", async () => {
+      const delegate = anchor.web3.Keypair.generate();
+      const periodCap = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 10);
+      const expiresAt = new anchor.BN(Math.floor(Date.now() / 1000) + 3600);
+
+      await program.methods
+        .grantAllowance(
+          delegate.publicKey,
+          new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 5),
+          new anchor.BN(86_400),
+          periodCap,
+          expiresAt
+        )
+        .rpc();
+
+      const balanceBefore = await provider.connection.getBalance(
+        recipient.publicKey
+      );
+
+      await program.methods
+        .delegateWithdraw(periodCap)
+        .accountsPartial({
+          delegate: delegate.publicKey,
+          vaultInfo: vault_info_pda,
+          vault: vault_pda,
+          recipient: recipient.publicKey,
+        })
+        .signers([delegate])
+        .rpc();
+
+      const balanceAfter = await provider.connection.getBalance(
+        recipient.publicKey
+      );
+
+      assert.equal(balanceAfter - balanceBefore, periodCap.toNumber());
+
+      // second withdrawal in the same period goes over the cap
+      try {
+        await program.methods
+          .delegateWithdraw(new anchor.BN(1))
+          .accountsPartial({
+            delegate: delegate.publicKey,
+            vaultInfo: vault_info_pda,
+            vault: vault_pda,
+            recipient: recipient.publicKey,
+          })
+          .signers([delegate])
+          .rpc();
+        assert.fail("withdrawal above the period cap should fail");
+      } catch (err) {
+        assert.include(err.toString(), "PeriodCapExceeded");
+      }
+    });
+  });
+
+  describe("payment stream", () => {
+    const recipient = anchor.web3.Keypair.generate();
+    const streamId = new anchor.BN(1);
+    const rate = new anchor.BN(1_000_000);
+
+    const [stream_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("stream"),
+        provider.publicKey.toBuffer(),
+        recipient.publicKey.toBuffer(),
+        streamId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    it("streams vested lamports to the recipient", async () => {
+      const now = Math.floor(Date.now() / 1000);
+
+      // started in the past so part of it is already vested
+      await program.methods
+        .createStream(
+          streamId,
+          recipient.publicKey,
+          rate,
+          new anchor.BN(now - 10),
+          new anchor.BN(now + 3600)
+        )
+        .rpc();
+
+      await program.methods
+        .withdrawStreamed()
+        .accountsPartial({
+          recipient: recipient.publicKey,
+          stream: stream_pda,
+        })
+        .signers([recipient])
+        .rpc();
+
+      const stream = await program.account.paymentStream.fetch(stream_pda);
+      const balance = await provider.connection.getBalance(
+        recipient.publicKey
+      );
+
+      assert.isAbove(stream.withdrawn.toNumber(), 0);
+      assert.equal(balance, stream.withdrawn.toNumber());
+    });
+
+    it("owner cancels and reclaims the rest", async () => {
+      await program.methods
+        .cancelStream()
+        .accountsPartial({
+          stream: stream_pda,
+          recipient: recipient.publicKey,
+        })
+        .rpc();
+
+      const account = await provider.connection.getAccountInfo(stream_pda);
+
+      assert.isNull(account);
+    });
   });
 });
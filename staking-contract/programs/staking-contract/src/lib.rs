@@ -6,6 +6,14 @@ const POINTS_PER_DAY:u64 = 1_000_000;
 const LAMPORTS_PER_SOL:u64 = 1_000_000_000;
 const SECONDS_PER_DAY:u64 = 86_400;
 
+// Loyalty tiers - the longer a position stays staked without a full unstake,
+// the more every second of staking is worth
+const BASE_MULTIPLIER_BPS:u64 = 10_000;
+const LOYALTY_TIER_1_SECONDS:u64 = 30 * SECONDS_PER_DAY;
+const LOYALTY_TIER_1_MULTIPLIER_BPS:u64 = 11_000; // +10%
+const LOYALTY_TIER_2_SECONDS:u64 = 90 * SECONDS_PER_DAY;
+const LOYALTY_TIER_2_MULTIPLIER_BPS:u64 = 12_500; // +25%
+
 #[program]
 pub mod staking_contract{
 
@@ -14,6 +22,101 @@ pub mod staking_contract{
     use super::*;
 
 
+    // Global config - only the program's upgrade authority can create it, and becomes the admin that runs the seasons
+    pub fn initialize_config(
+        context:Context<InitializeConfig>,
+        max_total_stake:u64,
+        max_stake_per_wallet:u64,
+        min_stake:u64,
+        min_remaining_balance:u64
+    )->Result<()>{
+        let config = &mut context.accounts.config;
+        let clock = Clock::get()?;
+
+        config.admin = context.accounts.admin.key();
+        config.current_season = 0;
+        config.season_start_time = clock.unix_timestamp;
+        config.total_staked = 0;
+        config.max_total_stake = max_total_stake;
+        config.max_stake_per_wallet = max_stake_per_wallet;
+        config.min_stake = min_stake;
+        config.min_remaining_balance = min_remaining_balance;
+        config.bump = context.bumps.config;
+
+        msg!("Config created, season {} started", config.current_season);
+
+        emit!(ConfigInitialized{
+            admin: config.admin,
+            season: config.current_season,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Freezes the current season and starts the next one.
+    // Stake accounts roll their points into the season history lazily, next time they are touched
+    pub fn end_season(context:Context<EndSeason>)->Result<()>{
+        let config = &mut context.accounts.config;
+        let clock = Clock::get()?;
+
+        require!(clock.unix_timestamp >= config.season_start_time, StakeError::InvalidTimestamp);
+
+        let ended_season = config.current_season;
+
+        config.current_season = config.current_season.checked_add(1).ok_or(StakeError::Overflow)?;
+        config.season_start_time = clock.unix_timestamp;
+
+        msg!("Season {} ended, season {} started", ended_season, config.current_season);
+
+        emit!(SeasonEnded{
+            ended_season,
+            new_season: config.current_season,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Caps for guarded launches, raised as the pool gets more battle tested
+    pub fn set_deposit_caps(context:Context<UpdateConfig>,max_total_stake:u64,max_stake_per_wallet:u64)->Result<()>{
+        let config = &mut context.accounts.config;
+        let clock = Clock::get()?;
+
+        config.max_total_stake = max_total_stake;
+        config.max_stake_per_wallet = max_stake_per_wallet;
+
+        emit!(DepositCapsUpdated{
+            max_total_stake,
+            max_stake_per_wallet,
+            total_staked: config.total_staked,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Dust protection - positions below these don't earn anything after truncation
+    pub fn set_stake_minimums(context:Context<UpdateConfig>,min_stake:u64,min_remaining_balance:u64)->Result<()>{
+        let config = &mut context.accounts.config;
+        let clock = Clock::get()?;
+
+        config.min_stake = min_stake;
+        config.min_remaining_balance = min_remaining_balance;
+
+        emit!(StakeMinimumsUpdated{
+            min_stake,
+            min_remaining_balance,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
     // Accounts with default data should get created 
     pub fn initialize_pda(context:Context<InitializePda>)->Result<()>{
         let owner = &context.accounts.signer;
@@ -25,6 +128,8 @@ pub mod staking_contract{
         pda_account.owner = *owner.key;
         pda_account.staked_amount = 0;
         pda_account.total_points = 0;
+        pda_account.stake_start_time = 0;
+        pda_account.season = context.accounts.config.current_season;
 
         let clock = clock::Clock::get()?;
 
@@ -32,6 +137,13 @@ pub mod staking_contract{
 
         msg!("PDA CREATED successfully");
 
+        emit!(StakeAccountInitialized{
+            user: pda_account.owner,
+            season: pda_account.season,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
@@ -54,8 +166,13 @@ pub mod staking_contract{
 
         let clock = clock::Clock::get()?;
 
+        roll_season(pda, &mut context.accounts.season_points, &context.accounts.config, context.bumps.season_points)?;
         update_points(pda, clock.unix_timestamp)?;
 
+        // streak starts with the first stake after the position was empty
+        if pda.staked_amount == 0 {
+            pda.stake_start_time = clock.unix_timestamp;
+        }
 
         let context_input = CpiContext::new(
                 system_program.to_account_info()
@@ -69,11 +186,28 @@ pub mod staking_contract{
         // also needs to increase the points first, if didn't do that then when we recalcultae then we end up adding more points
         pda.staked_amount = pda.staked_amount.checked_add(amount).ok_or(StakeError::Overflow)?;
 
+        let config = &mut context.accounts.config;
+        config.total_staked = config.total_staked.checked_add(amount).ok_or(StakeError::Overflow)?;
+
+        require!(pda.staked_amount>=config.min_stake, StakeError::BelowMinimumStake);
+        require!(pda.staked_amount<=config.max_stake_per_wallet, StakeError::WalletCapExceeded);
+        require!(config.total_staked<=config.max_total_stake, StakeError::PoolCapExceeded);
+
 
         msg!("Staked {} lamports, Total staked: {},Total Points:{}",
             amount, pda.staked_amount, pda.total_points/1_000_000
         );
 
+        emit!(Staked{
+            user: signer.key(),
+            amount,
+            staked_amount: pda.staked_amount,
+            total_points: pda.total_points,
+            stake_start_time: pda.stake_start_time,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
@@ -83,36 +217,46 @@ pub mod staking_contract{
 
         let signer = &context.accounts.signer;
         let pda = &mut context.accounts.pda_account;
-        let system_program = & context.accounts.system_program;
         let clock = Clock::get()?;
 
         require!(pda.staked_amount>=amount, StakeError::InsufficientStake);
 
-        update_points(pda, clock.unix_timestamp);
-        let signer_key = context.accounts.signer.key();
-        let signer_key_bytes = signer_key.as_ref();
-
-        let seed = &[
-            b"client",
-            signer_key_bytes,
-            &[pda.bump]
-        ];
+        roll_season(pda, &mut context.accounts.season_points, &context.accounts.config, context.bumps.season_points)?;
+        update_points(pda, clock.unix_timestamp)?;
 
-        let seeds: &[&[&[u8]]] = &[seed];
+        pda.staked_amount = pda.staked_amount.checked_sub(amount).ok_or(StakeError::Underflow)?;
 
+        // partial unstakes can't leave dust behind
+        require!(
+            pda.staked_amount == 0 || pda.staked_amount>=context.accounts.config.min_remaining_balance,
+            StakeError::BelowMinimumBalance
+        );
 
-        let cpiContext = CpiContext::new(system_program.to_account_info(), Transfer{
-            from: pda.to_account_info(),
-            to:signer.to_account_info()
-        }).with_signer(seeds);
+        let config = &mut context.accounts.config;
+        config.total_staked = config.total_staked.checked_sub(amount).ok_or(StakeError::Underflow)?;
 
-        transfer(cpiContext,pda.staked_amount)?;
+        // The stake account is owned by this program and carries data, so the system program
+        // can't debit it - the lamports are moved directly
+        **pda.to_account_info().try_borrow_mut_lamports()? -= amount;
+        **signer.to_account_info().try_borrow_mut_lamports()? += amount;
 
-        pda.staked_amount = pda.staked_amount.checked_sub(amount).ok_or(StakeError::Underflow)?;
+        // full unstake breaks the loyalty streak
+        if pda.staked_amount == 0 {
+            pda.stake_start_time = 0;
+        }
 
         msg!("Unstaked {} lamports. Remaining staked: {}, Total points: {}", 
              amount, pda.staked_amount, pda.total_points / 1_000_000);
 
+        emit!(Unstaked{
+            user: signer.key(),
+            amount,
+            staked_amount: pda.staked_amount,
+            total_points: pda.total_points,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
@@ -121,8 +265,11 @@ pub mod staking_contract{
         let pda = &mut context.accounts.pda;
         let clock = Clock::get()?;
 
+        // Settle the previous season first, only points of the current season are claimable
+        roll_season(pda, &mut context.accounts.season_points, &context.accounts.config, context.bumps.season_points)?;
+
         // Update poinst to current time 
-        update_points(pda, clock.unix_timestamp);
+        update_points(pda, clock.unix_timestamp)?;
 
 
 
@@ -130,6 +277,14 @@ pub mod staking_contract{
         
         msg!("User has {} claimable points",ClaimablePoints);
 
+        emit!(PointsClaimed{
+            user: pda.owner,
+            points: pda.total_points,
+            season: pda.season,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         pda.total_points = 0;
 
         Ok(())
@@ -141,7 +296,8 @@ pub mod staking_contract{
         let clock = Clock::get()?;
 
         let time_elapsed = clock.unix_timestamp.checked_sub(pda_account.last_update_time).ok_or(StakeError::InvalidTimestamp)? as u64;
-        let new_points = calculate_points_earned(pda_account.staked_amount, time_elapsed)?;
+        let streak = loyalty_streak_seconds(pda_account, clock.unix_timestamp)?;
+        let new_points = calculate_points_earned(pda_account.staked_amount, time_elapsed, streak)?;
 
         let current_total_points = pda_account.total_points.checked_add(new_points)
         .ok_or(StakeError::Overflow)?;
@@ -162,26 +318,116 @@ fn update_points(pda_account: &mut StakeAccount, current_time: i64) -> Result<()
         .ok_or(StakeError::InvalidTimestamp)? as u64;
     
     if time_elapsed > 0 && pda_account.staked_amount > 0 {
-        let new_points = calculate_points_earned(pda_account.staked_amount, time_elapsed)?;
+        let streak = loyalty_streak_seconds(pda_account, current_time)?;
+        let new_points = calculate_points_earned(pda_account.staked_amount, time_elapsed, streak)?;
         pda_account.total_points = pda_account.total_points.checked_add(new_points)
             .ok_or(StakeError::Overflow)?;
+
+        emit!(PointsUpdated{
+            user: pda_account.owner,
+            new_points,
+            total_points: pda_account.total_points,
+            season: pda_account.season,
+            timestamp: current_time,
+        });
     }
     
     pda_account.last_update_time = current_time;
     Ok(())
 }
 
-fn calculate_points_earned(staked_amount:u64,time_elapsed_in_seconds:u64)->Result<(u64)>{
-    
-    // Points = staked amount * time(in day) * poins per day
+// Moves the points of a finished season into its history account and restarts accrual at zero.
+// Points accrued up to the start of the current season are credited to the season the
+// account was last touched in. Only the latest season boundary is known on-chain, so if
+// several seasons ended since then they are collapsed into that history account -
+// `through_season` records the last season its points cover.
+fn roll_season(pda_account: &mut StakeAccount, season_points: &mut SeasonPoints, config: &ProgramConfig, season_points_bump: u8) -> Result<()> {
+    if season_points.owner == Pubkey::default() {
+        season_points.owner = pda_account.owner;
+        season_points.season = pda_account.season;
+        season_points.points = 0;
+        season_points.through_season = pda_account.season;
+        season_points.bump = season_points_bump;
+    }
+
+    if pda_account.season >= config.current_season {
+        return Ok(());
+    }
+
+    if pda_account.last_update_time < config.season_start_time {
+        update_points(pda_account, config.season_start_time)?;
+    }
+
+    season_points.points = season_points.points.checked_add(pda_account.total_points)
+        .ok_or(StakeError::Overflow)?;
+    season_points.through_season = config.current_season.checked_sub(1).ok_or(StakeError::Underflow)?;
+
+    msg!("Rolled {} points into season {}", season_points.points / 1_000_000, season_points.season);
+
+    emit!(SeasonRolled{
+        user: pda_account.owner,
+        season: season_points.season,
+        rolled_points: pda_account.total_points,
+        season_total_points: season_points.points,
+        new_season: config.current_season,
+    });
+
+    pda_account.total_points = 0;
+    pda_account.season = config.current_season;
+
+    Ok(())
+}
+
+// How long the position has been staked without interruption, 0 if it is empty
+fn loyalty_streak_seconds(pda_account: &StakeAccount, current_time: i64) -> Result<u64> {
+    if pda_account.staked_amount == 0 {
+        return Ok(0);
+    }
+
+    let streak = current_time.checked_sub(pda_account.stake_start_time)
+        .ok_or(StakeError::InvalidTimestamp)?;
+
+    Ok(streak.max(0) as u64)
+}
+
+// Sum of elapsed seconds weighted by the loyalty tier they fall in (in bps).
+// The elapsed window ends at `streak_seconds`, so a window crossing a tier
+// boundary only gets the bonus for the part after the boundary.
+fn loyalty_weighted_seconds(time_elapsed_in_seconds:u64, streak_seconds:u64)->Result<u128>{
+    let window_start = streak_seconds.saturating_sub(time_elapsed_in_seconds);
+
+    let tier_2_seconds = streak_seconds
+        .saturating_sub(window_start.max(LOYALTY_TIER_2_SECONDS));
+    let tier_1_seconds = streak_seconds.min(LOYALTY_TIER_2_SECONDS)
+        .saturating_sub(window_start.max(LOYALTY_TIER_1_SECONDS));
+    let base_seconds = time_elapsed_in_seconds
+        .checked_sub(tier_1_seconds + tier_2_seconds)
+        .ok_or(StakeError::Underflow)?;
+
+    let weighted = (base_seconds as u128 * BASE_MULTIPLIER_BPS as u128)
+        .checked_add(tier_1_seconds as u128 * LOYALTY_TIER_1_MULTIPLIER_BPS as u128)
+        .ok_or(StakeError::Overflow)?
+        .checked_add(tier_2_seconds as u128 * LOYALTY_TIER_2_MULTIPLIER_BPS as u128)
+        .ok_or(StakeError::Overflow)?;
+
+    Ok(weighted)
+}
+
+fn calculate_points_earned(staked_amount:u64,time_elapsed_in_seconds:u64,streak_seconds:u64)->Result<u64>{
+
+    let weighted_seconds = loyalty_weighted_seconds(time_elapsed_in_seconds, streak_seconds)?;
+
+    // Points = staked amount * time(in day) * poins per day * loyalty multiplier
     let points = (staked_amount as u128)
-        .checked_mul(time_elapsed_in_seconds as u128)
+        .checked_mul(weighted_seconds)
         .ok_or(StakeError::Overflow)?
         .checked_mul(POINTS_PER_DAY as u128)
         .ok_or(StakeError::Overflow)?
         .checked_div(LAMPORTS_PER_SOL as u128)
         .ok_or(StakeError::Overflow)?
         .checked_div(SECONDS_PER_DAY as u128)
+        .ok_or(StakeError::Overflow)?
+        .checked_div(BASE_MULTIPLIER_BPS as u128)
         .ok_or(StakeError::Overflow)?;
 
 
@@ -195,9 +441,77 @@ pub struct StakeAccount{
     pub staked_amount : u64, // 64 bits
     pub total_points : u64,
     pub last_update_time:i64,
+    pub stake_start_time:i64, // start of the current uninterrupted staking streak
+    pub season:u64, // season the total_points belong to
     pub bump:u8,
 }
 
+#[account]
+pub struct ProgramConfig{
+    pub admin: Pubkey,
+    pub current_season: u64,
+    pub season_start_time: i64,
+    pub total_staked: u64, // lamports staked across all accounts
+    pub max_total_stake: u64,
+    pub max_stake_per_wallet: u64,
+    pub min_stake: u64, // smallest position a stake can leave behind
+    pub min_remaining_balance: u64, // smallest position a partial unstake can leave behind
+    pub bump: u8,
+}
+
+// Points a user finished a season with, kept around for airdrop calculations
+#[account]
+pub struct SeasonPoints{
+    pub owner: Pubkey,
+    pub season: u64,
+    pub points: u64,
+    pub through_season: u64, // > season when the account wasn't touched in the seasons in between
+    pub bump: u8,
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info>{
+    #[account(mut)]
+    pub admin:Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program:Program<'info,crate::program::StakingContract>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @StakeError::Unauthorized)]
+    pub program_data:Account<'info,ProgramData>,
+    #[account(
+        init,
+        payer = admin,
+        space = 8+32+8+8+8+8+8+8+8+1,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config:Account<'info,ProgramConfig>,
+    pub system_program:Program<'info,System>
+}
+
+#[derive(Accounts)]
+pub struct EndSeason<'info>{
+    pub admin:Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @StakeError::Unauthorized
+    )]
+    pub config:Account<'info,ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info>{
+    pub admin:Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @StakeError::Unauthorized
+    )]
+    pub config:Account<'info,ProgramConfig>,
+}
+
 #[derive(Accounts)]
 pub struct InitializePda<'info>{
     #[account(mut)]
@@ -205,11 +519,16 @@ pub struct InitializePda<'info>{
     #[account(
         init,
         payer = signer,
-        space = 8+4+8+8+8+1,
+        space = 8+32+8+8+8+8+8+1,
         seeds = [b"client",signer.key().as_ref()],
         bump
     )]
     pub pda_account:Account<'info,StakeAccount>,
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config:Account<'info,ProgramConfig>,
     pub system_program:Program<'info,System>
 }
 
@@ -225,6 +544,20 @@ pub struct StakeIx<'info>{
         constraint = pda_account.owner == signer.key() @StakeError::Unauthorized
     )]
     pub pda_account : Account<'info,StakeAccount>,
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config:Account<'info,ProgramConfig>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8+32+8+8+8+1,
+        seeds = [b"season-points", signer.key().as_ref(), &pda_account.season.to_le_bytes()],
+        bump
+    )]
+    pub season_points:Account<'info,SeasonPoints>,
     pub system_program : Program<'info,System>
 }
 
@@ -241,6 +574,21 @@ pub struct UnStake<'info>{
     )]
     pub pda_account:Account<'info,StakeAccount>,
 
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config:Account<'info,ProgramConfig>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8+32+8+8+8+1,
+        seeds = [b"season-points", signer.key().as_ref(), &pda_account.season.to_le_bytes()],
+        bump
+    )]
+    pub season_points:Account<'info,SeasonPoints>,
+
     pub system_program:Program<'info,System>
 }
 
@@ -257,7 +605,23 @@ pub struct ClaimPoints<'info>{
         ],
         bump = pda.bump
     )]
-    pub pda: Account<'info,StakeAccount>
+    pub pda: Account<'info,StakeAccount>,
+
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump
+    )]
+    pub config:Account<'info,ProgramConfig>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        space = 8+32+8+8+8+1,
+        seeds = [b"season-points", signer.key().as_ref(), &pda.season.to_le_bytes()],
+        bump
+    )]
+    pub season_points:Account<'info,SeasonPoints>,
+
+    pub system_program:Program<'info,System>
 }
 
 
@@ -274,6 +638,99 @@ pub struct GetPoints<'info>{
 }
 
 
+// Events - every state transition is emitted so indexers don't have to parse msg! logs.
+// Points are raw (1_000_000 = 1 point), amounts in lamports
+
+#[event]
+pub struct ConfigInitialized{
+    pub admin: Pubkey,
+    pub season: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeasonEnded{
+    pub ended_season: u64,
+    pub new_season: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositCapsUpdated{
+    pub max_total_stake: u64,
+    pub max_stake_per_wallet: u64,
+    pub total_staked: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StakeMinimumsUpdated{
+    pub min_stake: u64,
+    pub min_remaining_balance: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StakeAccountInitialized{
+    pub user: Pubkey,
+    pub season: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Staked{
+    pub user: Pubkey,
+    pub amount: u64,
+    pub staked_amount: u64,
+    pub total_points: u64,
+    pub stake_start_time: i64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Unstaked{
+    pub user: Pubkey,
+    pub amount: u64,
+    pub staked_amount: u64,
+    pub total_points: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PointsClaimed{
+    pub user: Pubkey,
+    pub points: u64,
+    pub season: u64,
+    pub slot: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PointsUpdated{
+    pub user: Pubkey,
+    pub new_points: u64,
+    pub total_points: u64,
+    pub season: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeasonRolled{
+    pub user: Pubkey,
+    pub season: u64,
+    pub rolled_points: u64,
+    pub season_total_points: u64,
+    pub new_season: u64,
+}
+
+
 #[error_code]
 pub enum StakeError {
     #[msg("Amount must be greater than 0")]
@@ -288,4 +745,12 @@ pub enum StakeError {
     Underflow,
     #[msg("Invalid timestamp")]
     InvalidTimestamp,
+    #[msg("Stake would exceed the per wallet cap")]
+    WalletCapExceeded,
+    #[msg("Stake would exceed the pool cap")]
+    PoolCapExceeded,
+    #[msg("Position would be below the minimum stake")]
+    BelowMinimumStake,
+    #[msg("Remaining stake would be below the minimum balance")]
+    BelowMinimumBalance,
 }
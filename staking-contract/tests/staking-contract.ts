@@ -1,16 +1,363 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
+import { BankrunProvider, startAnchor } from "anchor-bankrun";
+import { Clock, ProgramTestContext } from "solana-bankrun";
+import { Keypair, PublicKey, SystemProgram } from "@solana/web3.js";
+import { assert } from "chai";
 import { StakingContract } from "../target/types/staking_contract";
+import IDL from "../target/idl/staking_contract.json";
 
+const LAMPORTS_PER_SOL = 1_000_000_000;
+const SECONDS_PER_DAY = 86_400;
+const BPF_LOADER_UPGRADEABLE = new PublicKey(
+  "BPFLoaderUpgradeab1e11111111111111111111111"
+);
+
+// Runs against bankrun instead of a validator, seasons and loyalty tiers need the clock moved by days
 describe("staking-contract", () => {
-  // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  let context: ProgramTestContext;
+  let provider: BankrunProvider;
+  let program: Program<StakingContract>;
+
+  // always wrap numbers in BN
+  const sol = (amount: number) => new anchor.BN(amount * LAMPORTS_PER_SOL);
+
+  const warp = async (seconds: number) => {
+    const current = await context.banksClient.getClock();
+    // a new slot also gives a new blockhash, so repeated instructions aren't deduplicated
+    context.warpToSlot(current.slot + BigInt(1));
+    context.setClock(
+      new Clock(
+        current.slot + BigInt(1),
+        current.epochStartTimestamp,
+        current.epoch,
+        current.leaderScheduleEpoch,
+        current.unixTimestamp + BigInt(seconds)
+      )
+    );
+  };
+
+  const stakeAccountPda = (user: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("client"), user.toBuffer()],
+      program.programId
+    )[0];
+
+  const seasonPointsPda = (user: PublicKey, season: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("season-points"),
+        user.toBuffer(),
+        season.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+  // season_points is derived from the season the stake account is in right now
+  const currentSeasonPoints = async (user: PublicKey) => {
+    const account = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user)
+    );
+    return seasonPointsPda(user, account.season);
+  };
+
+  const fundedKeypair = () => {
+    const user = Keypair.generate();
+
+    context.setAccount(user.publicKey, {
+      lamports: 1_000 * LAMPORTS_PER_SOL,
+      data: Buffer.alloc(0),
+      owner: SystemProgram.programId,
+      executable: false,
+    });
+
+    return user;
+  };
+
+  const newUser = async () => {
+    const user = fundedKeypair();
+
+    await program.methods
+      .initializePda()
+      .accounts({ signer: user.publicKey })
+      .signers([user])
+      .rpc();
+
+    return user;
+  };
+
+  const stake = async (user: Keypair, amount: anchor.BN) =>
+    program.methods
+      .stake(amount)
+      .accountsPartial({
+        signer: user.publicKey,
+        seasonPoints: await currentSeasonPoints(user.publicKey),
+      })
+      .signers([user])
+      .rpc();
+
+  const unstake = async (user: Keypair, amount: anchor.BN) =>
+    program.methods
+      .unstake(amount)
+      .accountsPartial({
+        signer: user.publicKey,
+        seasonPoints: await currentSeasonPoints(user.publicKey),
+      })
+      .signers([user])
+      .rpc();
+
+  const totalPoints = async (user: Keypair) =>
+    (
+      await program.account.stakeAccount.fetch(stakeAccountPda(user.publicKey))
+    ).totalPoints.toNumber();
+
+  const programData = () =>
+    PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      BPF_LOADER_UPGRADEABLE
+    )[0];
+
+  const initializeConfig = (admin: Keypair) =>
+    program.methods
+      .initializeConfig(sol(1_000), sol(100), sol(0.1), sol(0.1))
+      .accountsPartial({ admin: admin.publicKey, programData: programData() })
+      .signers([admin])
+      .rpc();
+
+  before(async () => {
+    context = await startAnchor(".", [], []);
+    provider = new BankrunProvider(context);
+    anchor.setProvider(provider);
 
-  const program = anchor.workspace.stakingContract as Program<StakingContract>;
+    program = new Program<StakingContract>(IDL as StakingContract, provider);
+
+    // make the test wallet the upgrade authority, only it may create the config
+    const programDataAccount = await context.banksClient.getAccount(programData());
+    const data = Buffer.from(programDataAccount.data);
+    data.writeUInt8(1, 12);
+    provider.wallet.publicKey.toBuffer().copy(data, 13);
+    context.setAccount(programData(), { ...programDataAccount, data });
+  });
+
+  it("only lets the upgrade authority create the config", async () => {
+    try {
+      await initializeConfig(fundedKeypair());
+      assert.fail("creating the config as a stranger should fail");
+    } catch (err) {
+      assert.include(err.toString(), "Unauthorized");
+    }
+  });
 
   it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+    await initializeConfig(context.payer);
+
+    const [config_pda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const config = await program.account.programConfig.fetch(config_pda);
+
+    assert.isTrue(config.admin.equals(provider.wallet.publicKey));
+    assert.equal(config.currentSeason.toNumber(), 0);
+    assert.equal(config.totalStaked.toNumber(), 0);
+  });
+
+  // 1 SOL for a day outside any tier is 1 point = 1_000_000 raw
+  it("weights stake windows by the loyalty tier they fall in", async () => {
+    const user = await newUser();
+
+    await stake(user, sol(1));
+
+    // days 0-40: 30 days at 1x, 10 days at 1.1x
+    await warp(40 * SECONDS_PER_DAY);
+    await stake(user, sol(1));
+    assert.equal(await totalPoints(user), 41_000_000);
+
+    // days 40-100 with 2 SOL: 50 days at 1.1x, 10 days at 1.25x
+    await warp(60 * SECONDS_PER_DAY);
+    await stake(user, sol(1));
+    assert.equal(await totalPoints(user), 41_000_000 + 2 * 67_500_000);
+  });
+
+  it("restarts the loyalty streak after a full unstake", async () => {
+    const user = await newUser();
+
+    await stake(user, sol(1));
+    await warp(100 * SECONDS_PER_DAY);
+    await unstake(user, sol(1));
+
+    const pointsBefore = await totalPoints(user);
+
+    await stake(user, sol(1));
+
+    // without the reset all 40 days would be in the top tier
+    await warp(40 * SECONDS_PER_DAY);
+    await stake(user, sol(1));
+    assert.equal(await totalPoints(user) - pointsBefore, 41_000_000);
+  });
+
+  it("only lets the admin end a season", async () => {
+    const stranger = await newUser();
+
+    try {
+      await program.methods
+        .endSeason()
+        .accounts({ admin: stranger.publicKey })
+        .signers([stranger])
+        .rpc();
+      assert.fail("ending a season as a non admin should fail");
+    } catch (err) {
+      assert.include(err.toString(), "Unauthorized");
+    }
+  });
+
+  it("rolls last season's points into the season history on the next touch", async () => {
+    const user = await newUser();
+
+    const { season } = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user.publicKey)
+    );
+
+    await stake(user, sol(1));
+    await warp(SECONDS_PER_DAY);
+
+    await program.methods
+      .endSeason()
+      .accounts({ admin: provider.wallet.publicKey })
+      .rpc();
+
+    // the account isn't touched at the season end, only a day later
+    await warp(SECONDS_PER_DAY);
+
+    await program.methods
+      .claimPoints()
+      .accountsPartial({
+        signer: user.publicKey,
+        seasonPoints: seasonPointsPda(user.publicKey, season),
+      })
+      .signers([user])
+      .rpc();
+
+    // only the day before the season ended counts towards it
+    const history = await program.account.seasonPoints.fetch(
+      seasonPointsPda(user.publicKey, season)
+    );
+    assert.equal(history.season.toNumber(), season.toNumber());
+    assert.equal(history.throughSeason.toNumber(), season.toNumber());
+    assert.equal(history.points.toNumber(), 1_000_000);
+
+    const account = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user.publicKey)
+    );
+    assert.equal(account.season.toNumber(), season.toNumber() + 1);
+  });
+
+  it("collapses seasons the account wasn't touched in into its last season", async () => {
+    const user = await newUser();
+
+    const { season } = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user.publicKey)
+    );
+
+    await stake(user, sol(1));
+
+    // two seasons end, a day apart, without the account being touched
+    for (let i = 0; i < 2; i++) {
+      await warp(SECONDS_PER_DAY);
+      await program.methods
+        .endSeason()
+        .accounts({ admin: provider.wallet.publicKey })
+        .rpc();
+    }
+
+    await warp(SECONDS_PER_DAY);
+
+    await program.methods
+      .claimPoints()
+      .accountsPartial({
+        signer: user.publicKey,
+        seasonPoints: seasonPointsPda(user.publicKey, season),
+      })
+      .signers([user])
+      .rpc();
+
+    // both days until the latest season started end up in the stale season's history
+    const history = await program.account.seasonPoints.fetch(
+      seasonPointsPda(user.publicKey, season)
+    );
+    assert.equal(history.points.toNumber(), 2_000_000);
+    assert.equal(history.throughSeason.toNumber(), season.toNumber() + 1);
+
+    const account = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user.publicKey)
+    );
+    assert.equal(account.season.toNumber(), season.toNumber() + 2);
+  });
+
+  it("rejects stakes above the wallet and pool caps", async () => {
+    const user = await newUser();
+
+    const [config_pda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("config")],
+      program.programId
+    );
+    const { totalStaked } = await program.account.programConfig.fetch(
+      config_pda
+    );
+
+    const setCaps = (maxTotalStake: anchor.BN, maxStakePerWallet: anchor.BN) =>
+      program.methods
+        .setDepositCaps(maxTotalStake, maxStakePerWallet)
+        .accounts({ admin: provider.wallet.publicKey })
+        .rpc();
+
+    await setCaps(sol(1_000), sol(2));
+
+    try {
+      await stake(user, sol(3));
+      assert.fail("stake above the wallet cap should fail");
+    } catch (err) {
+      assert.include(err.toString(), "WalletCapExceeded");
+    }
+
+    // room for one more SOL in the whole pool
+    await setCaps(totalStaked.add(sol(1)), sol(100));
+
+    try {
+      await stake(user, sol(2));
+      assert.fail("stake above the pool cap should fail");
+    } catch (err) {
+      assert.include(err.toString(), "PoolCapExceeded");
+    }
+
+    await setCaps(sol(1_000), sol(100));
+  });
+
+  it("enforces the minimum stake and remaining balance", async () => {
+    const user = await newUser();
+
+    try {
+      await stake(user, sol(0.05));
+      assert.fail("stake below the minimum should fail");
+    } catch (err) {
+      assert.include(err.toString(), "BelowMinimumStake");
+    }
+
+    await stake(user, sol(1));
+
+    try {
+      await unstake(user, sol(0.95));
+      assert.fail("unstake leaving dust behind should fail");
+    } catch (err) {
+      assert.include(err.toString(), "BelowMinimumBalance");
+    }
+
+    // a full unstake can go to zero
+    await unstake(user, sol(1));
+
+    const account = await program.account.stakeAccount.fetch(
+      stakeAccountPda(user.publicKey)
+    );
+    assert.equal(account.stakedAmount.toNumber(), 0);
   });
 });
@@ -0,0 +1,206 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenAccount};
+use anchor_spl::token_interface::spl_token_2022::{extension::{transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions}, state::Account as Token2022Account};
+use spl_discriminator::SplDiscriminate;
+use spl_tlv_account_resolution::{account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+use staking_token_contract::{cpi::{accounts::TransferPosition, transfer_position}, program::StakingTokenContract};
+
+declare_id!("3v1wGeiTaHa42EFGHLJiCpyCqaFCGiofc7AbXxvBj4v3");
+
+// Token-2022 calls `execute` on every transfer of a staking receipt. The hook hands the
+// transfer to staking_token_contract so the staked position follows the receipt.
+#[program]
+pub mod receipt_hook{
+
+    use super::*;
+
+    // Called by staking_token_contract at initialize, signed by the receipt mint authority
+    pub fn initialize_extra_account_meta_list(ctx:Context<InitializeExtraAccountMetaList>)->Result<()>{
+
+        let mut data = ctx.accounts.extra_account_meta_list.try_borrow_mut_data()?;
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut data, &extra_account_metas()?)?;
+
+        Ok(())
+    }
+
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn execute(ctx:Context<Execute>,amount:u64)->Result<()>{
+
+        // only token-2022 sets this flag, stops anyone from calling the hook directly
+        check_is_transferring(&ctx.accounts.source)?;
+
+        // moving receipts between your own accounts doesn't move the position
+        if ctx.accounts.source.owner == ctx.accounts.destination.owner {
+            return Ok(());
+        }
+
+        let bump = &[ctx.bumps.hook_authority];
+        let signer_seeds = &[&[
+            b"hook-auth".as_ref(),
+            bump,
+        ][..]];
+
+        let cpi_accounts = TransferPosition{
+            hook_authority: ctx.accounts.hook_authority.to_account_info(),
+            pgm_info: ctx.accounts.pgm_info.to_account_info(),
+            receipt_mint: ctx.accounts.mint.to_account_info(),
+            source: ctx.accounts.source.to_account_info(),
+            destination: ctx.accounts.destination.to_account_info(),
+            sender_info: ctx.accounts.sender_info.to_account_info(),
+            receiver_info: ctx.accounts.receiver_info.to_account_info(),
+            reward_streams: ctx.accounts.reward_streams.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.staking_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_position(cpi_ctx, amount)
+    }
+
+}
+
+// Extra accounts token-2022 resolves for `execute`, in order after
+// source(0), mint(1), destination(2), owner(3), extra_account_meta_list(4)
+fn extra_account_metas()->Result<Vec<ExtraAccountMeta>>{
+    Ok(vec![
+        // 5 - staking program
+        ExtraAccountMeta::new_with_pubkey(&staking_token_contract::ID, false, false)?,
+        // 6 - staking config
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[Seed::Literal { bytes: b"config".to_vec() }],
+            false,
+            false
+        )?,
+        // 7 - sender's UserInfo, owner is at offset 32 of the token account
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[
+                Seed::Literal { bytes: b"user-info".to_vec() },
+                Seed::AccountData { account_index: 0, data_index: 32, length: 32 },
+            ],
+            false,
+            true
+        )?,
+        // 8 - receiver's UserInfo
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[
+                Seed::Literal { bytes: b"user-info".to_vec() },
+                Seed::AccountData { account_index: 2, data_index: 32, length: 32 },
+            ],
+            false,
+            true
+        )?,
+        // 9 - hook authority, signs the CPI into the staking program
+        ExtraAccountMeta::new_with_seeds(
+            &[Seed::Literal { bytes: b"hook-auth".to_vec() }],
+            false,
+            false
+        )?,
+        // 10 - staking reward streams, checkpointed on every position change
+        ExtraAccountMeta::new_external_pda_with_seeds(
+            5,
+            &[Seed::Literal { bytes: b"reward-streams".to_vec() }],
+            false,
+            true
+        )?,
+    ])
+}
+
+fn check_is_transferring(source:&InterfaceAccount<TokenAccount>)->Result<()>{
+    let source_info = source.to_account_info();
+    let data = source_info.try_borrow_data()?;
+    let account = StateWithExtensions::<Token2022Account>::unpack(&data)?;
+    let extension = account.get_extension::<TransferHookAccount>()?;
+
+    require!(bool::from(extension.transferring), HookError::NotTransferring);
+
+    Ok(())
+}
+
+
+#[derive(Accounts)]
+pub struct InitializeExtraAccountMetaList<'info>{
+    #[account(mut)]
+    pub payer : Signer<'info>,
+
+    pub authority : Signer<'info>,
+
+    /// CHECK: initialised as a TLV account by the handler
+    #[account(
+        init,
+        payer = payer,
+        space = ExtraAccountMetaList::size_of(extra_account_metas()?.len())?,
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list : AccountInfo<'info>,
+
+    #[account(
+        constraint = mint.mint_authority == Some(authority.key()).into() @HookError::Unauthorized
+    )]
+    pub mint : InterfaceAccount<'info,Mint>,
+
+    pub system_program : Program<'info,System>,
+}
+
+// Order is fixed by the transfer hook interface
+#[derive(Accounts)]
+pub struct Execute<'info>{
+    #[account(
+        token::mint = mint,
+    )]
+    pub source : InterfaceAccount<'info,TokenAccount>,
+
+    pub mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        token::mint = mint,
+    )]
+    pub destination : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK: source owner or delegate, already checked by token-2022
+    pub owner : UncheckedAccount<'info>,
+
+    /// CHECK: validated by its seeds
+    #[account(
+        seeds = [b"extra-account-metas", mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list : UncheckedAccount<'info>,
+
+    pub staking_program : Program<'info,StakingTokenContract>,
+
+    /// CHECK: checked by the staking program
+    pub pgm_info : UncheckedAccount<'info>,
+
+    /// CHECK: checked by the staking program
+    #[account(mut)]
+    pub sender_info : UncheckedAccount<'info>,
+
+    /// CHECK: checked by the staking program
+    #[account(mut)]
+    pub receiver_info : UncheckedAccount<'info>,
+
+    /// CHECK: PDA signer only
+    #[account(
+        seeds = [b"hook-auth"],
+        bump
+    )]
+    pub hook_authority : UncheckedAccount<'info>,
+
+    /// CHECK: checked by the staking program
+    #[account(mut)]
+    pub reward_streams : UncheckedAccount<'info>,
+}
+
+
+#[error_code]
+pub enum HookError{
+    #[msg("Hook can only run during a token transfer")]
+    NotTransferring,
+
+    #[msg("Signer is not the mint authority")]
+    Unauthorized,
+}
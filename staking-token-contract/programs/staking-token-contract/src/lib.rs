@@ -1,16 +1,62 @@
+// anchor generates cpi/client wrappers with every instruction argument
+#![allow(clippy::too_many_arguments)]
+
 use anchor_lang::prelude::*;
-use anchor_spl::{  associated_token::AssociatedToken,  token_interface::{set_authority,Transfer, Mint,MintTo, SetAuthority,TokenAccount, TokenInterface,transfer,mint_to}};
+use anchor_lang::solana_program::hash::hashv;
+use anchor_spl::token_interface::spl_token_2022::{self, extension::{BaseStateWithExtensions, ExtensionType, StateWithExtensions}};
+use anchor_lang::solana_program::{instruction::{AccountMeta, Instruction}, program::invoke_signed};
+use anchor_spl::{  associated_token::{get_associated_token_address_with_program_id, AssociatedToken}, token_2022::Token2022,  token_interface::{set_authority, Burn, TransferChecked, Mint,MintTo, SetAuthority,TokenAccount, TokenInterface,burn,transfer_checked,mint_to}};
 use std::mem::size_of;
 
 declare_id!("AtHB3oVn9q5bnYpLXmrHUfX5rrw25Q1bpg9DhfCw2Hcy");
 
+// one bit per leaf, keeps the bitmap account under the 10KiB CPI allocation limit
+const MAX_DISTRIBUTOR_NODES:u64 = 80_000;
+
+// approvals are tracked as a bitmask, one bit per signer
+const MAX_MULTISIG_SIGNERS:usize = 10;
+
+// ~1 day at 400ms slots, stakers get this much notice before a config change lands
+const MIN_TIMELOCK_SLOTS:u64 = 216_000;
+const MAX_PENDING_CHANGES:usize = 16;
+// id + largest ConfigChange variant + eta
+const PENDING_CHANGE_SIZE:usize = 8 + 1 + 32 + 8;
+
+// partner reward streams, on top of the minted reward
+const MAX_REWARD_STREAMS:usize = 4;
+const REWARD_STREAM_SIZE:usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 16;
+const REWARD_PRECISION:u128 = 1_000_000_000_000;
+
+// ~4 years at 400ms slots, longest lock a stake_for grant can carry
+const MAX_LOCK_SLOTS:u64 = 315_360_000;
+
+// positions per crank_compound call, keeps the transaction inside the compute budget
+const MAX_CRANK_BATCH:usize = 8;
+
+const MAX_FEE_BPS:u16 = 2_000;
+const BPS_DENOMINATOR:u128 = 10_000;
+
 /// CHECK:
 #[program]
 pub mod staking_token_contract{
 
     use super::*;
 
-    pub fn initialize(ctx:Context<Initialize>,reward:u64,start_slot:u64,end_slot:u64)->Result<()>{
+    pub fn initialize(
+        ctx:Context<Initialize>,
+        reward:u64,
+        start_slot:u64,
+        end_slot:u64,
+        fee_bps:u16,
+        max_total_stake:u64,
+        max_stake_per_wallet:u64,
+        min_stake:u64,
+        mint_policy:MintPolicy
+    )->Result<()>{
+
+        require!(fee_bps<=MAX_FEE_BPS, StakeError::FeeTooHigh);
+
+        check_mint_extensions(&ctx.accounts.token_mint, &mint_policy)?;
 
         let program_config = &mut ctx.accounts.program_config;
         let owner = &ctx.accounts.owner;
@@ -21,6 +67,32 @@ pub mod staking_token_contract{
         program_config.token_mint = ctx.accounts.token_mint.key();
         program_config.start_slot = start_slot;
         program_config.end_slot = end_slot;
+        program_config.reward_per_token_stored = 0;
+        program_config.reward_index_slot = Clock::get()?.slot;
+        program_config.paused = false;
+        program_config.admin_multisig = None;
+        program_config.fee_bps = fee_bps;
+        program_config.treasury = ctx.accounts.treasury.key();
+        program_config.total_fees_collected = 0;
+        program_config.total_staked = 0;
+        program_config.max_total_stake = max_total_stake;
+        program_config.max_stake_per_wallet = max_stake_per_wallet;
+        program_config.min_stake = min_stake;
+        program_config.allowlist_root = None;
+        program_config.mint_policy = mint_policy;
+        program_config.minting_disabled = false;
+        program_config.receipt_mint = ctx.accounts.receipt_mint.key();
+        program_config.receipt_hook = ctx.accounts.receipt_hook.key();
+        program_config.hook_authority = Pubkey::find_program_address(&[b"hook-auth"], &ctx.accounts.receipt_hook.key()).0;
+
+        let timelock = &mut ctx.accounts.timelock;
+        timelock.next_id = 0;
+        timelock.pending = Vec::new();
+        timelock.bump = ctx.bumps.timelock;
+
+        let reward_streams = &mut ctx.accounts.reward_streams;
+        reward_streams.streams = Vec::new();
+        reward_streams.bump = ctx.bumps.reward_streams;
 
 
         let program_auth = &ctx.accounts.program_auth;
@@ -45,16 +117,66 @@ pub mod staking_token_contract{
             Some(program_auth.key())
         )?;
 
+        // The receipt hook needs to know which accounts to pull in on every receipt transfer.
+        // Its init instruction is signed by the receipt mint authority, which is our PDA.
+        let bump = &[program_config.auth_bump];
+        let signer_seeds = &[&[
+            b"auth".as_ref(),
+            bump,
+        ][..]];
+
+        let init_hook_ix = Instruction{
+            program_id: ctx.accounts.receipt_hook.key(),
+            accounts: vec![
+                AccountMeta::new(owner.key(), true),
+                AccountMeta::new_readonly(program_auth.key(), true),
+                AccountMeta::new(ctx.accounts.extra_account_meta_list.key(), false),
+                AccountMeta::new_readonly(ctx.accounts.receipt_mint.key(), false),
+                AccountMeta::new_readonly(ctx.accounts.system_program.key(), false),
+            ],
+            // anchor sighash of receipt_hook::initialize_extra_account_meta_list
+            data: hashv(&[b"global:initialize_extra_account_meta_list"]).to_bytes()[..8].to_vec(),
+        };
+
+        invoke_signed(
+            &init_hook_ix,
+            &[
+                owner.to_account_info(),
+                program_auth.to_account_info(),
+                ctx.accounts.extra_account_meta_list.to_account_info(),
+                ctx.accounts.receipt_mint.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                ctx.accounts.receipt_hook.to_account_info(),
+            ],
+            signer_seeds
+        )?;
+
+        let clock = Clock::get()?;
+
+        emit!(Initialized{
+            owner: program_config.owner,
+            token_mint: program_config.token_mint,
+            reward_rate_per_token_per_slot: reward,
+            start_slot,
+            end_slot,
+            fee_bps,
+            treasury: program_config.treasury,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
         Ok(())
     }
 
 
-    pub fn stake(ctx:Context<Stake>,amount:u64)->Result<()>{
+    // `proof` is only checked when the pool is allowlisted and the position is being opened.
+    // The user only signs as authority, rent comes from `fee_payer`.
+    pub fn stake(ctx:Context<Stake>,amount:u64,proof:Vec<[u8;32]>)->Result<()>{
       msg!("Stake: User {} stakings {} tokens ",ctx.accounts.user.key(),amount);
 
       require!(amount>0, StakeError::ZeroAMount);
 
-      let pgm_info = &ctx.accounts.program_info;
+      let pgm_info = &mut ctx.accounts.program_info;
 
       let user_info = &mut ctx.accounts.user_info;
 
@@ -70,20 +192,29 @@ pub mod staking_token_contract{
 
       let signer = &ctx.accounts.user;
 
-      if user_info.amount>0 {
-        // user has staked earlier, first calculate the reward_debt
-        let rewards = calculate_reward(&user_info, &pgm_info, clock.slot)?;
+      // fresh account from init_if_needed
+      if user_info.owner == Pubkey::default() {
+        check_allowlist(pgm_info, &signer.key(), &proof)?;
+        user_info.owner = signer.key();
+      }
 
-        if rewards>0 {
-            mint_reward(rewards, &ctx.accounts.token_mint, user_ata, pgm_authority, &pgm_info, &ctx.accounts.token_program)?;
-        }
+      // pay what the user has earned so far, including rewards booked by a receipt
+      // transfer while the position was empty
+      let rewards = calculate_reward(user_info, pgm_info, clock.slot)?;
+
+      if rewards>0 {
+          mint_reward(rewards, &ctx.accounts.token_mint, user_ata, &ctx.accounts.treasury, pgm_authority, pgm_info, &ctx.accounts.token_program)?;
       }
+      user_info.accrued_rewards = 0;
+
 
+      let vault_before = token_vault.amount;
 
       // Transfer user token to our valut 
-      let cpi_accounts = Transfer{
+      let cpi_accounts = TransferChecked{
         authority: signer.to_account_info(),
         from:user_ata.to_account_info(),
+        mint:ctx.accounts.token_mint.to_account_info(),
         to:token_vault.to_account_info(),
       };
 
@@ -92,44 +223,339 @@ pub mod staking_token_contract{
 
       let cpi_context = CpiContext::new(transfer_cpi_program, cpi_accounts);
 
-      transfer(
+      transfer_checked(
         cpi_context,
-        amount
+        amount,
+        ctx.accounts.token_mint.decimals
       )?;
 
+      // Token-2022 transfer fees are withheld in the vault, only credit what actually arrived
+      ctx.accounts.token_vault.reload()?;
+      let received = ctx.accounts.token_vault.amount.checked_sub(vault_before).ok_or(StakeError::Underflow)?;
+
+      require!(received>0, StakeError::ZeroAMount);
+
+    update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+    settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+
     //   update the user_info
 
-    user_info.amount = user_info.amount.checked_add(amount).ok_or(StakeError::Overflow)?;
+    user_info.amount = user_info.amount.checked_add(received).ok_or(StakeError::Overflow)?;
     user_info.deposit_slot = clock.slot;
+    user_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
     user_info.reward_debt = 0; // reset as user is already rewarded
 
+    pgm_info.total_staked = pgm_info.total_staked.checked_add(received).ok_or(StakeError::Overflow)?;
+
+    require!(user_info.amount>=pgm_info.min_stake, StakeError::BelowMinimumStake);
+    require!(user_info.amount<=pgm_info.max_stake_per_wallet, StakeError::WalletCapExceeded);
+    require!(pgm_info.total_staked<=pgm_info.max_total_stake, StakeError::PoolCapExceeded);
+
+    // receipts are minted 1:1 with what was credited
+    let bump = &[pgm_info.auth_bump];
+    let signer_seeds = &[&[
+        b"auth".as_ref(),
+        bump,
+    ][..]];
+
+    let cpi_accounts = MintTo{
+        authority: ctx.accounts.program_auth.to_account_info(),
+        mint: ctx.accounts.receipt_mint.to_account_info(),
+        to: ctx.accounts.user_receipt_ata.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.receipt_token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+    mint_to(cpi_ctx, received)?;
+
+    emit!(Staked{
+        user: signer.key(),
+        amount: received,
+        rewards_paid: rewards,
+        staked_amount: user_info.amount,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    });
 
       Ok(())
     }
 
-    pub fn un_stake(ctx:Context<UnStake>)->Result<()>{
+    // Deposits `amount` from the payer into the beneficiary's position. Only the beneficiary
+    // can withdraw it. Locked deposits go through `stake_grant` instead.
+    pub fn stake_for(ctx:Context<StakeFor>,amount:u64,proof:Vec<[u8;32]>)->Result<()>{
+        msg!("StakeFor: {} staking {} tokens for {}", ctx.accounts.payer.key(), amount, ctx.accounts.beneficiary.key());
+
+        require!(amount>0, StakeError::ZeroAMount);
+
+        let pgm_info = &mut ctx.accounts.program_info;
+        let user_info = &mut ctx.accounts.user_info;
+        let beneficiary = ctx.accounts.beneficiary.key();
+
+        let clock = Clock::get()?;
+
+        // fresh account from init_if_needed
+        if user_info.owner == Pubkey::default() {
+            check_allowlist(pgm_info, &beneficiary, &proof)?;
+            user_info.owner = beneficiary;
+        }
+
+        // the beneficiary's pending rewards are booked, not paid to the payer
+        settle_rewards(user_info, pgm_info, clock.slot)?;
+
+        let vault_before = ctx.accounts.token_vault.amount;
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.payer.to_account_info(),
+            from: ctx.accounts.payer_ata.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.token_vault.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_context, amount, ctx.accounts.token_mint.decimals)?;
+
+        ctx.accounts.token_vault.reload()?;
+        let received = ctx.accounts.token_vault.amount.checked_sub(vault_before).ok_or(StakeError::Underflow)?;
+
+        require!(received>0, StakeError::ZeroAMount);
+
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+
+        user_info.amount = user_info.amount.checked_add(received).ok_or(StakeError::Overflow)?;
+
+        pgm_info.total_staked = pgm_info.total_staked.checked_add(received).ok_or(StakeError::Overflow)?;
+
+        require!(user_info.amount>=pgm_info.min_stake, StakeError::BelowMinimumStake);
+        require!(user_info.amount<=pgm_info.max_stake_per_wallet, StakeError::WalletCapExceeded);
+        require!(pgm_info.total_staked<=pgm_info.max_total_stake, StakeError::PoolCapExceeded);
+
+        let bump = &[pgm_info.auth_bump];
+        let signer_seeds = &[&[
+            b"auth".as_ref(),
+            bump,
+        ][..]];
+
+        let cpi_accounts = MintTo{
+            authority: ctx.accounts.program_auth.to_account_info(),
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            to: ctx.accounts.beneficiary_receipt_ata.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.receipt_token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        mint_to(cpi_ctx, received)?;
+
+        emit!(StakedFor{
+            payer: ctx.accounts.payer.key(),
+            beneficiary,
+            amount: received,
+            staked_amount: user_info.amount,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Vesting grant: the payer deposits `amount` into a separate position of the beneficiary,
+    // locked until `lock_until_slot`. It has no receipts and never touches the beneficiary's
+    // own position, so it needs no co-signature. Only the beneficiary can claim it.
+    pub fn stake_grant(ctx:Context<StakeGrant>,id:u64,amount:u64,lock_until_slot:u64,proof:Vec<[u8;32]>)->Result<()>{
+        msg!("StakeGrant: {} granting {} tokens to {}", ctx.accounts.payer.key(), amount, ctx.accounts.beneficiary.key());
+
+        require!(amount>0, StakeError::ZeroAMount);
+
+        let pgm_info = &mut ctx.accounts.program_info;
+        let grant_info = &mut ctx.accounts.grant_info;
+        let beneficiary = ctx.accounts.beneficiary.key();
+
+        let clock = Clock::get()?;
+
+        require!(lock_until_slot>clock.slot, StakeError::InvalidBlockGap);
+        require!(lock_until_slot<=clock.slot.saturating_add(MAX_LOCK_SLOTS), StakeError::LockTooLong);
+
+        check_allowlist(pgm_info, &beneficiary, &proof)?;
+
+        grant_info.owner = beneficiary;
+        grant_info.locked_until_slot = lock_until_slot;
+        grant_info.deposit_slot = clock.slot;
+        grant_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
+
+        let vault_before = ctx.accounts.token_vault.amount;
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.payer.to_account_info(),
+            from: ctx.accounts.payer_ata.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.token_vault.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_context, amount, ctx.accounts.token_mint.decimals)?;
+
+        ctx.accounts.token_vault.reload()?;
+        let received = ctx.accounts.token_vault.amount.checked_sub(vault_before).ok_or(StakeError::Underflow)?;
+
+        require!(received>0, StakeError::ZeroAMount);
+
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, grant_info)?;
+
+        grant_info.amount = received;
+
+        pgm_info.total_staked = pgm_info.total_staked.checked_add(received).ok_or(StakeError::Overflow)?;
+
+        require!(grant_info.amount>=pgm_info.min_stake, StakeError::BelowMinimumStake);
+        require!(grant_info.amount<=pgm_info.max_stake_per_wallet, StakeError::WalletCapExceeded);
+        require!(pgm_info.total_staked<=pgm_info.max_total_stake, StakeError::PoolCapExceeded);
+
+        emit!(GrantStaked{
+            payer: ctx.accounts.payer.key(),
+            beneficiary,
+            id,
+            amount: received,
+            locked_until_slot: lock_until_slot,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Pays a grant's rewards to the beneficiary. Once the lock is over it also returns the
+    // principal and closes the grant, unless stream rewards are still owed.
+    // remaining accounts, optional: (stream vault, reward token account, stream mint) for each reward stream to pay out
+    pub fn claim_grant<'info>(ctx:Context<'_, '_, 'info, 'info, ClaimGrant<'info>>,id:u64)->Result<()>{
+        msg!("ClaimGrant: {} claiming grant {}", ctx.accounts.beneficiary.key(), id);
+
+        let clock = Clock::get()?;
+
+        let pgm_info = &mut ctx.accounts.pgm_info;
+        let grant_info = &mut ctx.accounts.grant_info;
+        let beneficiary_ata = &ctx.accounts.beneficiary_ata;
+        let treasury = &ctx.accounts.treasury;
+        let token_mint = &ctx.accounts.token_mint;
+        let mint_auth = &ctx.accounts.mint_auth;
+        let token_pgm = &ctx.accounts.token_pgm;
+
+        let rewards = calculate_reward(grant_info, pgm_info, clock.slot)?;
+
+        if rewards>0 {
+            mint_reward(rewards, token_mint, beneficiary_ata, treasury, mint_auth, pgm_info, token_pgm)?;
+        }
+
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, grant_info)?;
+
+        let streams_paid = if ctx.remaining_accounts.is_empty() {
+            false
+        } else {
+            pay_stream_rewards(
+                &ctx.accounts.reward_streams,
+                grant_info,
+                beneficiary_ata.owner,
+                ctx.remaining_accounts,
+                mint_auth,
+                pgm_info.auth_bump,
+                token_pgm
+            )?
+        };
+
+        let principal = if clock.slot>=grant_info.locked_until_slot { grant_info.amount } else { 0 };
+
+        require!(rewards>0 || streams_paid || principal>0, StakeError::ZeroAMount);
+
+        if principal>0 {
+            let bump = &[pgm_info.auth_bump];
+            let signer_seeds = &[&[
+                b"auth".as_ref(),
+                bump,
+            ][..]];
+
+            let cpi_accounts = TransferChecked{
+                authority: mint_auth.to_account_info(),
+                from: ctx.accounts.vault.to_account_info(),
+                mint: token_mint.to_account_info(),
+                to: beneficiary_ata.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(token_pgm.to_account_info(), cpi_accounts, signer_seeds);
+
+            transfer_checked(cpi_context, principal, token_mint.decimals)?;
+
+            pgm_info.total_staked = pgm_info.total_staked.checked_sub(principal).ok_or(StakeError::Underflow)?;
+            grant_info.amount = 0;
+        }
+
+        grant_info.deposit_slot = clock.slot;
+        grant_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
+        grant_info.reward_debt = 0;
+        grant_info.accrued_rewards = 0;
+
+        emit!(GrantClaimed{
+            beneficiary: ctx.accounts.beneficiary.key(),
+            id,
+            amount: principal,
+            rewards_paid: rewards,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        if grant_info.amount==0 && grant_info.stream_owed.iter().all(|owed| *owed==0) {
+            grant_info.close(ctx.accounts.beneficiary.to_account_info())?;
+        }
+
+        Ok(())
+    }
+
+    // remaining accounts, optional: (stream vault, reward token account, stream mint) for each reward stream to pay out
+    pub fn un_stake<'info>(ctx:Context<'_, '_, 'info, 'info, UnStake<'info>>)->Result<()>{
         msg!("Unstake: User {} unstaking all tokens", ctx.accounts.user.key());
 
         let clock = Clock::get()?;
 
-        let pgm_info = &ctx.accounts.pgm_info;
+        let pgm_info = &mut ctx.accounts.pgm_info;
         let user_info = &mut ctx.accounts.user_info;
         let user_ata = &ctx.accounts.user_ata;
+        // rewards can go to another wallet, principal always returns to the user
+        let reward_ata = ctx.accounts.recipient.as_ref().unwrap_or(user_ata);
+        let treasury = &ctx.accounts.treasury;
         let token_mint = &ctx.accounts.token_mint;
         // have the authority of vault
         let pgm_auth = &ctx.accounts.mint_auth;
         let token_pgm = &ctx.accounts.token_pgm;
         let vault = &ctx.accounts.vault;
 
-        let rewards = calculate_reward(user_info, &pgm_info, clock.slot)?;
+        let rewards = calculate_reward(user_info, pgm_info, clock.slot)?;
 
         if rewards>0 {
-            mint_reward(rewards, token_mint, user_ata, pgm_auth, pgm_info, token_pgm)?;
+            mint_reward(rewards, token_mint, reward_ata, treasury, pgm_auth, pgm_info, token_pgm)?;
         }
 
         let amount_to_unstake = user_info.amount;
 
         require!(amount_to_unstake>0,StakeError::NotStaked);
+        require!(user_info.pending_slash_amount==0, StakeError::SlashPending);
+
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+
+        // stream payouts are optional here, so a stuck stream can't hold the principal.
+        // Whatever isn't paid stays in stream_owed and can be claimed with claim_points.
+        if !ctx.remaining_accounts.is_empty() {
+            pay_stream_rewards(
+                &ctx.accounts.reward_streams,
+                user_info,
+                reward_ata.owner,
+                ctx.remaining_accounts,
+                pgm_auth,
+                pgm_info.auth_bump,
+                token_pgm
+            )?;
+        }
 
         // CPI To transfer token also need to send the seeds
 
@@ -141,9 +567,10 @@ pub mod staking_token_contract{
         ][..];
 
         // Here we are transferring token from our vault to another ata
-        let cpi_acccounts = Transfer{
+        let cpi_acccounts = TransferChecked{
             authority: pgm_auth.to_account_info(),
             from:vault.to_account_info(),
+            mint:token_mint.to_account_info(),
             to: user_ata.to_account_info(),
         };
 
@@ -153,302 +580,2929 @@ pub mod staking_token_contract{
 
         let cpi_context = CpiContext::new_with_signer(cpi_program, cpi_acccounts, signer_seeds);
 
-        transfer(cpi_context, amount_to_unstake)?;
+        // any transfer fee on the way out is paid by the user
+        transfer_checked(cpi_context, amount_to_unstake, token_mint.decimals)?;
+
+        pgm_info.total_staked = pgm_info.total_staked.checked_sub(amount_to_unstake).ok_or(StakeError::Underflow)?;
+
+        // the position is gone, so are its receipts
+        let cpi_accounts = Burn{
+            authority: ctx.accounts.user.to_account_info(),
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            from: ctx.accounts.user_receipt_ata.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(ctx.accounts.receipt_token_program.to_account_info(), cpi_accounts);
+
+        burn(cpi_ctx, amount_to_unstake)?;
+
+        emit!(Unstaked{
+            user: ctx.accounts.user.key(),
+            recipient: reward_ata.owner,
+            amount: amount_to_unstake,
+            rewards_paid: rewards,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        user_info.amount = 0;
+        user_info.deposit_slot = clock.slot;
+        user_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
+        user_info.reward_debt = 0;
+        user_info.accrued_rewards = 0;
 
-        
-        // The user_info account is closed and rent refunded to the user.
-        // The state is effectively reset.
+        // The user_info account is closed and rent refunded to the user,
+        // unless it still holds unpaid stream rewards.
+        if user_info.stream_owed.iter().all(|owed| *owed==0) {
+            user_info.close(ctx.accounts.user.to_account_info())?;
+        }
 
         Ok(())
     }
 
-    pub fn claim_points(ctx:Context<ClaimPoints>)->Result<()>{
+    // Pays the minted reward and the chosen reward streams at once.
+    // remaining accounts: (stream vault, reward token account, stream mint) for each reward stream
+    // to pay out, the vault picks the stream. Streams left out stay owed.
+    pub fn claim_points<'info>(ctx:Context<'_, '_, 'info, 'info, ClaimPoints<'info>>)->Result<()>{
 
         msg!("Claim Rewards: User {} claiming rewards", ctx.accounts.user.key());
 
         let clock = Clock::get()?;
-        let pgm_info = &ctx.accounts.pgm_info;
+        let pgm_info = &mut ctx.accounts.pgm_info;
         let user_info= &mut ctx.accounts.user_info;
-        let user_ata = &ctx.accounts.user_ata;
+        let user_ata = ctx.accounts.recipient.as_ref().unwrap_or(&ctx.accounts.user_ata);
+        let treasury = &ctx.accounts.treasury;
         let mint_auth = &ctx.accounts.mint_auth;
         let token_pgm = &ctx.accounts.token_pgm;
         let token_mint = &ctx.accounts.token_mint;
 
-        let mut rewards = calculate_reward(user_info, pgm_info, clock.slot)?;
+        let rewards = calculate_reward(user_info, pgm_info, clock.slot)?;
 
         msg!("Hardcoded rewards: {}", rewards);
-        
-        require!(rewards>0,StakeError::ZeroAMount);
 
-        mint_reward(rewards, token_mint, user_ata, mint_auth, pgm_info, token_pgm)?;
-        msg!("Rewards minted successfully!");
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+        let streams_paid = pay_stream_rewards(
+            &ctx.accounts.reward_streams,
+            user_info,
+            user_ata.owner,
+            ctx.remaining_accounts,
+            mint_auth,
+            pgm_info.auth_bump,
+            token_pgm
+        )?;
+
+        require!(rewards>0 || streams_paid, StakeError::ZeroAMount);
+
+        if rewards>0 {
+            mint_reward(rewards, token_mint, user_ata, treasury, mint_auth, pgm_info, token_pgm)?;
+            msg!("Rewards minted successfully!");
+        }
 
         user_info.deposit_slot = clock.slot;
+        user_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
         user_info.reward_debt = 0;
+        user_info.accrued_rewards = 0;
+
+        emit!(RewardsClaimed{
+            user: ctx.accounts.user.key(),
+            recipient: user_ata.owner,
+            amount: rewards,
+            staked_amount: user_info.amount,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
 
         Ok(())
     }
 
-}
+    // claim_points for users without SOL: the relayer signs as fee payer and may take
+    // up to `relayer_fee` of the minted reward, the user only signs as authority.
+    // remaining accounts: (stream vault, reward token account, stream mint) for each reward stream
+    // to pay out, the vault picks the stream. Streams left out stay owed.
+    pub fn claim_points_relayed<'info>(ctx:Context<'_, '_, 'info, 'info, ClaimPointsRelayed<'info>>,relayer_fee:u64)->Result<()>{
 
+        msg!("Claim Rewards: User {} claiming rewards via {}", ctx.accounts.user.key(), ctx.accounts.relayer.key());
 
-fn calculate_reward(
-    user_info:&UserInfo,
-    contract_info: &ContractInfo,
-    current_slot:u64
-)->Result<u64>{
-    let time_elapsed = current_slot.checked_sub(user_info.deposit_slot).ok_or(StakeError::Underflow)?;
+        let clock = Clock::get()?;
+        let pgm_info = &mut ctx.accounts.pgm_info;
+        let user_info= &mut ctx.accounts.user_info;
+        let user_ata = &ctx.accounts.user_ata;
+        let relayer_ata = &ctx.accounts.relayer_ata;
+        let treasury = &ctx.accounts.treasury;
+        let mint_auth = &ctx.accounts.mint_auth;
+        let token_pgm = &ctx.accounts.token_pgm;
+        let token_mint = &ctx.accounts.token_mint;
 
-    let result = user_info.amount.checked_mul(time_elapsed).ok_or(StakeError::Underflow)?
-        .checked_mul(contract_info.reward_rate_per_token_per_slot).ok_or(StakeError::Overflow)?
-    .checked_sub(user_info.reward_debt).ok_or(StakeError::Underflow)?;
+        let rewards = calculate_reward(user_info, pgm_info, clock.slot)?;
 
-    Ok(result)
-}
+        require!(relayer_fee<=rewards, StakeError::RelayerFeeTooHigh);
 
-fn mint_reward<'info>(
-    amount:u64,
-    mint: &InterfaceAccount<'info,Mint>,
-    to: &InterfaceAccount<'info,TokenAccount>,
-    authority:&AccountInfo<'info>,
-    program_info:&Account<'info,ContractInfo>,
-    token_program: &Interface<'info,TokenInterface>,
-)->Result<()>{
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+        let streams_paid = pay_stream_rewards(
+            &ctx.accounts.reward_streams,
+            user_info,
+            user_ata.owner,
+            ctx.remaining_accounts,
+            mint_auth,
+            pgm_info.auth_bump,
+            token_pgm
+        )?;
 
-    msg!("mint_reward called with amount: {}", amount);
-    msg!("Minting to account: {}", to.key());
-    msg!("Using authority: {}", authority.key());
+        require!(rewards>0 || streams_paid, StakeError::ZeroAMount);
 
-//  Cpi Call karni hai - Pda has authority so have to pass the Seeds 
-    let bump = &[program_info.auth_bump];
-    let signer_seeds = &[&[
-        b"auth".as_ref(),
-        bump,
-    ][..]];
+        // both parts pay the protocol fee
+        let user_rewards = rewards - relayer_fee;
 
-    let cpi_accounts = MintTo{
-        authority:authority.to_account_info(),
-        mint:mint.to_account_info(),
-        to:to.to_account_info()
-    };
+        if user_rewards>0 {
+            mint_reward(user_rewards, token_mint, user_ata, treasury, mint_auth, pgm_info, token_pgm)?;
+        }
 
-    let cpi_program = token_program.to_account_info();
+        if relayer_fee>0 {
+            mint_reward(relayer_fee, token_mint, relayer_ata, treasury, mint_auth, pgm_info, token_pgm)?;
+        }
 
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+        user_info.deposit_slot = clock.slot;
+        user_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
+        user_info.reward_debt = 0;
+        user_info.accrued_rewards = 0;
+
+        emit!(RewardsClaimed{
+            user: ctx.accounts.user.key(),
+            recipient: user_ata.owner,
+            amount: rewards,
+            staked_amount: user_info.amount,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        emit!(RelayerFeePaid{
+            user: ctx.accounts.user.key(),
+            relayer: ctx.accounts.relayer.key(),
+            amount: relayer_fee,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
 
-    msg!("About to call mint_to with amount: {}", amount);
-    mint_to(cpi_ctx, amount)?;
-    msg!("mint_to completed successfully");
+        Ok(())
+    }
 
+    // Permissionless. Mints each position's pending reward into the vault and adds it to the
+    // stake, with receipts to match. A position that would go over a cap has the reward
+    // booked into `accrued_rewards` instead. Nothing can be sent anywhere but the owner's position.
+    // remaining accounts: (user_info, owner's receipt ata) for every position, at most MAX_CRANK_BATCH
+    pub fn crank_compound<'info>(ctx:Context<'_, '_, 'info, 'info, CrankCompound<'info>>)->Result<()>{
 
-    Ok(())
-}
+        let remaining = ctx.remaining_accounts;
 
-#[account]
-pub struct ContractInfo{
-    pub owner:Pubkey,
-    pub start_slot:u64,
-    pub end_slot:u64,
-    pub token_mint:Pubkey,
-    pub reward_rate_per_token_per_slot:u64,
-    pub auth_bump:u8,
-    pub vault_bump:u8,
-}
+        let positions = remaining.chunks_exact(2);
 
-#[account]
-pub struct UserInfo{
-    pub amount:u64,
-    pub deposit_slot:u64,
-    pub reward_debt:u64
-}
+        require!(positions.remainder().is_empty(), StakeError::InvalidCrankAccounts);
+        require!(positions.len()<=MAX_CRANK_BATCH, StakeError::CrankBatchTooLarge);
 
-// Intializing Contract 
-// 1) PDA - to store all account config
-// 2) Token min create karna hai
-// 3) PDA to have a authority to sign 
-// 4) PDA created from token that can store all the token - vault
-#[derive(Accounts)]
-pub struct Initialize<'info>{
-    #[account(mut)]
-    pub owner : Signer<'info>,
+        let clock = Clock::get()?;
+        let pgm_info = &mut ctx.accounts.pgm_info;
+        let treasury = &ctx.accounts.treasury;
+        let token_mint = &ctx.accounts.token_mint;
+        let vault = &ctx.accounts.vault;
+        let mint_auth = &ctx.accounts.mint_auth;
+        let token_pgm = &ctx.accounts.token_pgm;
 
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + size_of::<ContractInfo>(),
-        seeds = [b"config"],
-        bump
-    )]
-    pub program_config : Account<'info , ContractInfo>,
+        // checkpoint once at the old total, every position below settles against it
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
 
-    // have to change the token authority to a PDA so that pgm can freely sign it 
-    #[account(
-        mut,
-        mint::authority = owner,
-    )]
-    pub token_mint : InterfaceAccount<'info,Mint>,
+        let bump = &[pgm_info.auth_bump];
+        let signer_seeds = &[&[
+            b"auth".as_ref(),
+            bump,
+        ][..]];
 
-    // Inside the function we have to make a CPI call to pass the authority to this 
-      /// CHECK: 
-    #[account(
-        seeds = [b"auth"],
-        bump
-    )]
-    pub program_auth : AccountInfo<'info>,
+        for accounts in positions {
+            let (user_info_account, receipt_ata) = (&accounts[0], &accounts[1]);
 
-    #[account(
-        init,
-        payer = owner,
-        seeds = [
-            b"vault", token_mint.key().as_ref()
-        ],
-        bump,
-        token::mint = token_mint,
-        token::authority = program_auth,
-    )]
-    pub program_vault: InterfaceAccount<'info,TokenAccount>,
+            let mut user_info = Account::<UserInfo>::try_from(user_info_account)?;
 
-    pub token_program : Interface<'info,TokenInterface>,
-    pub system_program : Program<'info, System>,
-}
+            let (expected_user_info, _) = Pubkey::find_program_address(&[b"user-info", user_info.owner.as_ref()], ctx.program_id);
+            require_keys_eq!(user_info_account.key(), expected_user_info, StakeError::InvalidCrankAccounts);
 
+            let expected_receipt_ata = get_associated_token_address_with_program_id(&user_info.owner, &pgm_info.receipt_mint, &Token2022::id());
+            require_keys_eq!(receipt_ata.key(), expected_receipt_ata, StakeError::InvalidCrankAccounts);
 
+            let rewards = calculate_reward(&user_info, pgm_info, clock.slot)?;
 
-#[derive(Accounts)]
-pub struct Stake<'info>{
+            if rewards==0 {
+                continue;
+            }
 
-    #[account(mut)]
-    pub user : Signer<'info>,
+            let compounded = rewards.checked_sub(protocol_fee(rewards, pgm_info.fee_bps)?).ok_or(StakeError::Underflow)?;
+            let new_amount = user_info.amount.checked_add(compounded).ok_or(StakeError::Overflow)?;
+            let new_total = pgm_info.total_staked.checked_add(compounded).ok_or(StakeError::Overflow)?;
 
-    #[account(
-        seeds = [b"config"],
-        bump
-    )]
-    pub program_info : Account<'info,ContractInfo>,
+            if new_amount>pgm_info.max_stake_per_wallet || new_total>pgm_info.max_total_stake {
+                settle_rewards(&mut user_info, pgm_info, clock.slot)?;
+                user_info.exit(ctx.program_id)?;
+                continue;
+            }
+
+            settle_stream_rewards(&ctx.accounts.reward_streams, &mut user_info)?;
+
+            mint_reward(rewards, token_mint, vault, treasury, mint_auth, pgm_info, token_pgm)?;
+
+            let cpi_accounts = MintTo{
+                authority: mint_auth.to_account_info(),
+                mint: ctx.accounts.receipt_mint.to_account_info(),
+                to: receipt_ata.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.receipt_token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+            mint_to(cpi_ctx, compounded)?;
+
+            user_info.amount = new_amount;
+            user_info.deposit_slot = clock.slot;
+            user_info.reward_per_token_paid = reward_per_token(pgm_info, clock.slot)?;
+            user_info.reward_debt = 0;
+            user_info.accrued_rewards = 0;
+            user_info.exit(ctx.program_id)?;
+
+            pgm_info.total_staked = new_total;
+
+            emit!(RewardsCompounded{
+                user: user_info.owner,
+                amount: compounded,
+                staked_amount: user_info.amount,
+                slot: clock.slot,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Queues a slash of `amount` on a bonded position. Bounded by max_slash_bps of the position
+    // and by slash_epoch_cap across all users per epoch (a cancelled slash still counts).
+    // The position can't be withdrawn or transferred until the slash is executed or cancelled,
+    // and it can only be executed once its appeal window is over.
+    pub fn slash(ctx:Context<Slash>,amount:u64,reason:u16)->Result<()>{
+
+        require!(amount>0, StakeError::ZeroAMount);
+
+        let clock = Clock::get()?;
+        let pgm_info = &mut ctx.accounts.pgm_info;
+        let user_info = &mut ctx.accounts.user_info;
+
+        require!(user_info.pending_slash_amount==0, StakeError::SlashPending);
+
+        let max_slash = (user_info.amount as u128)
+            .checked_mul(pgm_info.max_slash_bps as u128)
+            .ok_or(StakeError::Overflow)?
+            / BPS_DENOMINATOR;
+        require!(amount as u128<=max_slash, StakeError::SlashTooLarge);
+
+        if clock.epoch!=pgm_info.slash_epoch {
+            pgm_info.slash_epoch = clock.epoch;
+            pgm_info.slashed_this_epoch = 0;
+        }
+
+        pgm_info.slashed_this_epoch = pgm_info.slashed_this_epoch.checked_add(amount).ok_or(StakeError::Overflow)?;
+        require!(pgm_info.slashed_this_epoch<=pgm_info.slash_epoch_cap, StakeError::SlashEpochCapExceeded);
+
+        user_info.pending_slash_amount = amount;
+        user_info.pending_slash_reason = reason;
+        user_info.appeal_until_slot = clock.slot.checked_add(pgm_info.appeal_window_slots).ok_or(StakeError::Overflow)?;
+
+        emit!(SlashQueued{
+            user: user_info.owner,
+            slasher: ctx.accounts.slasher.key(),
+            amount,
+            reason,
+            appeal_until_slot: user_info.appeal_until_slot,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Permissionless once the appeal window is over: seizes the queued amount into the slash
+    // destination. Receipts for the slashed part are burned, the program is the receipt
+    // mint's permanent delegate.
+    pub fn execute_slash(ctx:Context<ExecuteSlash>)->Result<()>{
+
+        let clock = Clock::get()?;
+        let pgm_info = &mut ctx.accounts.pgm_info;
+        let user_info = &mut ctx.accounts.user_info;
+
+        let amount = user_info.pending_slash_amount;
+
+        require!(amount>0, StakeError::NoPendingSlash);
+        require!(clock.slot>=user_info.appeal_until_slot, StakeError::AppealPending);
+
+        // rewards earned on the slashed part so far are kept
+        settle_rewards(user_info, pgm_info, clock.slot)?;
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, user_info)?;
+
+        user_info.amount = user_info.amount.checked_sub(amount).ok_or(StakeError::Underflow)?;
+        user_info.pending_slash_amount = 0;
+
+        pgm_info.total_staked = pgm_info.total_staked.checked_sub(amount).ok_or(StakeError::Underflow)?;
+
+        let bump = &[pgm_info.auth_bump];
+        let signer_seeds = &[&[
+            b"auth".as_ref(),
+            bump,
+        ][..]];
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.program_auth.to_account_info(),
+            from: ctx.accounts.vault.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.slash_destination.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, ctx.accounts.token_mint.decimals)?;
+
+        let cpi_accounts = Burn{
+            authority: ctx.accounts.program_auth.to_account_info(),
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            from: ctx.accounts.user_receipt_account.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(ctx.accounts.receipt_token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        burn(cpi_ctx, amount)?;
+
+        emit!(Slashed{
+            user: user_info.owner,
+            amount,
+            reason: user_info.pending_slash_reason,
+            staked_amount: user_info.amount,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Upholds an appeal: drops the queued slash. Owner signs until a multisig takes over,
+    // after that it needs an approved CancelSlash proposal.
+    pub fn cancel_slash(ctx:Context<CancelSlash>)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        authorize_admin_instruction(
+            &AdminAction::CancelSlash { user: ctx.accounts.user.key() },
+            &ctx.accounts.pgm_info,
+            Some(ctx.accounts.authority.key()),
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+        )?;
+
+        let user_info = &mut ctx.accounts.user_info;
+        let amount = user_info.pending_slash_amount;
+
+        require!(amount>0, StakeError::NoPendingSlash);
+
+        user_info.pending_slash_amount = 0;
+        user_info.appeal_until_slot = 0;
+
+        emit!(SlashCancelled{
+            user: user_info.owner,
+            amount,
+            reason: user_info.pending_slash_reason,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Publishes the merkle root of (index, user, amount) leaves for a finished season
+    // and moves the airdrop budget into a vault owned by the program
+    pub fn create_distributor(
+        ctx:Context<CreateDistributor>,
+        season:u64,
+        root:[u8;32],
+        max_total_claim:u64,
+        max_num_nodes:u64,
+        clawback_slot:u64
+    )->Result<()>{
+
+        require!(max_total_claim>0, StakeError::ZeroAMount);
+        require!(max_num_nodes>0 && max_num_nodes<=MAX_DISTRIBUTOR_NODES, StakeError::TooManyNodes);
+
+        let clock = Clock::get()?;
+        require!(clawback_slot>clock.slot, StakeError::InvalidBlockGap);
+
+        authorize_admin_instruction(
+            &AdminAction::CreateDistributor { season, root, max_total_claim, max_num_nodes, clawback_slot },
+            &ctx.accounts.pgm_info,
+            Some(ctx.accounts.funder.key()),
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+        )?;
+
+        let distributor = &mut ctx.accounts.distributor;
+
+        distributor.season = season;
+        distributor.root = root;
+        distributor.mint = ctx.accounts.token_mint.key();
+        distributor.max_total_claim = max_total_claim;
+        distributor.max_num_nodes = max_num_nodes;
+        distributor.total_claimed = 0;
+        distributor.num_claimed = 0;
+        distributor.clawback_slot = clawback_slot;
+        distributor.clawed_back = false;
+        distributor.bump = ctx.bumps.distributor;
+        distributor.vault_bump = ctx.bumps.distributor_vault;
+
+        let claim_bitmap = &mut ctx.accounts.claim_bitmap;
+        claim_bitmap.distributor = distributor.key();
+        claim_bitmap.bits = vec![0; bitmap_len(max_num_nodes)];
+
+        // Fund the airdrop vault
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.funder.to_account_info(),
+            from: ctx.accounts.funder_ata.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.distributor_vault.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_context, max_total_claim, ctx.accounts.token_mint.decimals)?;
+
+        msg!("Distributor for season {} created with {} tokens", season, max_total_claim);
+
+        emit!(DistributorCreated{
+            distributor: distributor.key(),
+            season,
+            root,
+            max_total_claim,
+            max_num_nodes,
+            clawback_slot,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn claim_airdrop(ctx:Context<ClaimAirdrop>,index:u64,amount:u64,proof:Vec<[u8;32]>)->Result<()>{
+
+        msg!("Claim Airdrop: User {} claiming {} tokens", ctx.accounts.claimant.key(), amount);
+
+        let clock = Clock::get()?;
+        let distributor = &mut ctx.accounts.distributor;
+        let claim_bitmap = &mut ctx.accounts.claim_bitmap;
+
+        require!(!distributor.clawed_back && clock.slot<distributor.clawback_slot, StakeError::ClaimWindowClosed);
+        require!(index<distributor.max_num_nodes, StakeError::InvalidDistributorIndex);
+        require!(!is_claimed(claim_bitmap, index), StakeError::AlreadyClaimed);
+
+        let leaf = hashv(&[
+            &index.to_le_bytes(),
+            ctx.accounts.claimant.key().as_ref(),
+            &amount.to_le_bytes(),
+        ]).to_bytes();
+
+        require!(verify_proof(&proof, distributor.root, leaf), StakeError::InvalidProof);
+
+        distributor.total_claimed = distributor.total_claimed.checked_add(amount).ok_or(StakeError::Overflow)?;
+        require!(distributor.total_claimed<=distributor.max_total_claim, StakeError::ExceedsMaxClaim);
+        distributor.num_claimed = distributor.num_claimed.checked_add(1).ok_or(StakeError::Overflow)?;
+
+        set_claimed(claim_bitmap, index);
+
+        let bump = &[ctx.accounts.pgm_info.auth_bump];
+        let signer_seeds = &[&[
+            b"auth".as_ref(),
+            bump,
+        ][..]];
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.program_auth.to_account_info(),
+            from: ctx.accounts.distributor_vault.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.claimant_ata.to_account_info(),
+        };
+
+        let cpi_context = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_context, amount, ctx.accounts.token_mint.decimals)?;
+
+        emit!(AirdropClaimed{
+            distributor: distributor.key(),
+            season: distributor.season,
+            index,
+            claimant: ctx.accounts.claimant.key(),
+            amount,
+            total_claimed: distributor.total_claimed,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // After the deadline the admin takes back whatever was not claimed
+    pub fn clawback(ctx:Context<Clawback>)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        authorize_admin_instruction(
+            &AdminAction::Clawback { season: ctx.accounts.distributor.season, destination: ctx.accounts.destination.key() },
+            &ctx.accounts.pgm_info,
+            Some(ctx.accounts.authority.key()),
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+        )?;
+
+        let distributor = &mut ctx.accounts.distributor;
+
+        require!(clock.slot>=distributor.clawback_slot, StakeError::ClawbackNotStarted);
+        require!(!distributor.clawed_back, StakeError::AlreadyClawedBack);
+
+        distributor.clawed_back = true;
+
+        let remaining = ctx.accounts.distributor_vault.amount;
+
+        if remaining>0 {
+            let bump = &[ctx.accounts.pgm_info.auth_bump];
+            let signer_seeds = &[&[
+                b"auth".as_ref(),
+                bump,
+            ][..]];
+
+            let cpi_accounts = TransferChecked{
+                authority: ctx.accounts.program_auth.to_account_info(),
+                from: ctx.accounts.distributor_vault.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.destination.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new_with_signer(ctx.accounts.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+            transfer_checked(cpi_context, remaining, ctx.accounts.token_mint.decimals)?;
+        }
+
+        msg!("Clawed back {} tokens from season {} distributor", remaining, distributor.season);
+
+        emit!(AirdropClawedBack{
+            distributor: distributor.key(),
+            season: distributor.season,
+            amount: remaining,
+            destination: ctx.accounts.destination.key(),
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Single owner admin path, only available until a multisig takes over
+    pub fn execute_admin_action(ctx:Context<ExecuteAdminAction>,action:AdminAction)->Result<()>{
+        apply_admin_action(
+            &action,
+            &mut ctx.accounts.pgm_info,
+            &mut ctx.accounts.timelock,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.program_auth,
+            &ctx.accounts.token_program,
+            None
+        )
+    }
+
+    // Hands the admin rights of the config over to an M-of-N multisig.
+    // From here on admin actions go through proposals.
+    pub fn create_multisig(ctx:Context<CreateMultisig>,signers:Vec<Pubkey>,threshold:u8)->Result<()>{
+
+        require!(!signers.is_empty() && signers.len()<=MAX_MULTISIG_SIGNERS, StakeError::InvalidMultisigSigners);
+        require!(threshold>0 && threshold as usize<=signers.len(), StakeError::InvalidThreshold);
+
+        for (i, signer) in signers.iter().enumerate() {
+            require!(!signers[..i].contains(signer), StakeError::InvalidMultisigSigners);
+        }
+
+        let multisig = &mut ctx.accounts.multisig;
+
+        multisig.signers = signers;
+        multisig.threshold = threshold;
+        multisig.proposal_count = 0;
+        multisig.bump = ctx.bumps.multisig;
+
+        ctx.accounts.pgm_info.admin_multisig = Some(multisig.key());
+
+        let clock = Clock::get()?;
+
+        emit!(MultisigCreated{
+            multisig: multisig.key(),
+            signers: multisig.signers.clone(),
+            threshold,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Proposer's approval is counted right away
+    pub fn propose(ctx:Context<Propose>,action:AdminAction)->Result<()>{
+
+        let multisig = &mut ctx.accounts.multisig;
+        let signer_index = multisig_signer_index(multisig, &ctx.accounts.proposer.key())?;
+
+        let proposal = &mut ctx.accounts.proposal;
+
+        proposal.multisig = multisig.key();
+        proposal.index = multisig.proposal_count;
+        proposal.proposer = ctx.accounts.proposer.key();
+        proposal.action = action.clone();
+        proposal.approvals = 1<<signer_index;
+        proposal.executed = false;
+        proposal.bump = ctx.bumps.proposal;
+
+        multisig.proposal_count = multisig.proposal_count.checked_add(1).ok_or(StakeError::Overflow)?;
+
+        let clock = Clock::get()?;
+
+        emit!(ProposalCreated{
+            proposal: proposal.key(),
+            index: proposal.index,
+            proposer: proposal.proposer,
+            action,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve(ctx:Context<Approve>)->Result<()>{
+
+        let signer_index = multisig_signer_index(&ctx.accounts.multisig, &ctx.accounts.signer.key())?;
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(!proposal.executed, StakeError::ProposalAlreadyExecuted);
+        require!(proposal.approvals & (1<<signer_index) == 0, StakeError::AlreadyApproved);
+
+        proposal.approvals |= 1<<signer_index;
+
+        let clock = Clock::get()?;
+
+        emit!(ProposalApproved{
+            proposal: proposal.key(),
+            signer: ctx.accounts.signer.key(),
+            approvals: proposal.approvals.count_ones() as u8,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Anyone can execute once the threshold is met
+    pub fn execute_proposal(ctx:Context<ExecuteProposal>)->Result<()>{
+
+        let proposal = &mut ctx.accounts.proposal;
+
+        require!(!proposal.executed, StakeError::ProposalAlreadyExecuted);
+        require!(proposal.approvals.count_ones()>=ctx.accounts.multisig.threshold as u32, StakeError::ThresholdNotMet);
+
+        proposal.executed = true;
+
+        apply_admin_action(
+            &proposal.action,
+            &mut ctx.accounts.pgm_info,
+            &mut ctx.accounts.timelock,
+            &ctx.accounts.token_mint,
+            &ctx.accounts.program_auth,
+            &ctx.accounts.token_program,
+            Some(proposal.key())
+        )
+    }
+
+    // Permissionless, anyone can apply a queued change once its eta has passed
+    pub fn execute_change(ctx:Context<ExecuteChange>,id:u64)->Result<()>{
+
+        let timelock = &mut ctx.accounts.timelock;
+        let pgm_info = &mut ctx.accounts.pgm_info;
+
+        let position = timelock.pending.iter()
+            .position(|pending| pending.id==id)
+            .ok_or(StakeError::ChangeNotFound)?;
+
+        let clock = Clock::get()?;
+
+        require!(clock.slot>=timelock.pending[position].eta, StakeError::TimelockNotElapsed);
+
+        let pending = timelock.pending.remove(position);
+
+        match pending.change {
+            ConfigChange::RewardRate { reward_rate } => {
+                // slots before this one keep the old rate
+                checkpoint_reward_index(pgm_info, clock.slot)?;
+                pgm_info.reward_rate_per_token_per_slot = reward_rate;
+            }
+            ConfigChange::EndSlot { end_slot } => {
+                require!(end_slot>pgm_info.start_slot, StakeError::InvalidBlockGap);
+                checkpoint_reward_index(pgm_info, clock.slot)?;
+                pgm_info.end_slot = end_slot;
+            }
+            ConfigChange::FeeBps { fee_bps } => {
+                require!(fee_bps<=MAX_FEE_BPS, StakeError::FeeTooHigh);
+                pgm_info.fee_bps = fee_bps;
+            }
+            ConfigChange::Treasury { treasury } => {
+                let new_treasury = ctx.accounts.new_treasury.as_ref().ok_or(StakeError::InvalidTreasury)?;
+
+                require!(new_treasury.key()==treasury, StakeError::InvalidTreasury);
+                require!(new_treasury.mint==pgm_info.token_mint, StakeError::InvalidMint);
+
+                pgm_info.treasury = treasury;
+            }
+        }
+
+        emit!(ConfigChangeExecuted{
+            id,
+            change: pending.change,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Lets a wallet receive stake receipts without staking first
+    pub fn open_position(ctx:Context<OpenPosition>,proof:Vec<[u8;32]>)->Result<()>{
+
+        check_allowlist(&ctx.accounts.pgm_info, &ctx.accounts.user.key(), &proof)?;
+
+        let slot = Clock::get()?.slot;
+        let user_info = &mut ctx.accounts.user_info;
+        user_info.owner = ctx.accounts.user.key();
+        user_info.deposit_slot = slot;
+        user_info.reward_per_token_paid = reward_per_token(&ctx.accounts.pgm_info, slot)?;
+
+        Ok(())
+    }
+
+    // Only callable by the receipt hook, moves `amount` of staked balance along with
+    // the receipts. Pending rewards of both sides are settled into `accrued_rewards` first.
+    pub fn transfer_position(ctx:Context<TransferPosition>,amount:u64)->Result<()>{
+
+        let pgm_info = &ctx.accounts.pgm_info;
+        let sender_info = &mut ctx.accounts.sender_info;
+        let receiver_info = &mut ctx.accounts.receiver_info;
+
+        let clock = Clock::get()?;
+
+        // the queued slash needs the position and its receipts to still be there
+        require!(sender_info.pending_slash_amount==0, StakeError::SlashPending);
+
+        settle_rewards(sender_info, pgm_info, clock.slot)?;
+        settle_rewards(receiver_info, pgm_info, clock.slot)?;
+
+        update_reward_streams(&mut ctx.accounts.reward_streams, pgm_info.total_staked, clock.slot)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, sender_info)?;
+        settle_stream_rewards(&ctx.accounts.reward_streams, receiver_info)?;
+
+        sender_info.amount = sender_info.amount.checked_sub(amount).ok_or(StakeError::Underflow)?;
+        receiver_info.amount = receiver_info.amount.checked_add(amount).ok_or(StakeError::Overflow)?;
+
+        require!(sender_info.amount==0 || sender_info.amount>=pgm_info.min_stake, StakeError::BelowMinimumStake);
+        require!(receiver_info.amount>=pgm_info.min_stake, StakeError::BelowMinimumStake);
+        require!(receiver_info.amount<=pgm_info.max_stake_per_wallet, StakeError::WalletCapExceeded);
+
+        emit!(PositionTransferred{
+            from: sender_info.owner,
+            to: receiver_info.owner,
+            amount,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Partner co-incentives: `budget` of `reward_mint` is streamed to stakers pro rata
+    // between start_slot and end_slot. The owner approves, the funder pays.
+    pub fn add_reward_stream(ctx:Context<AddRewardStream>,index:u8,budget:u64,start_slot:u64,end_slot:u64)->Result<()>{
+
+        let reward_streams = &mut ctx.accounts.reward_streams;
+
+        require!(reward_streams.streams.len()<MAX_REWARD_STREAMS, StakeError::TooManyRewardStreams);
+        require!(index as usize==reward_streams.streams.len(), StakeError::InvalidStreamIndex);
+        require!(end_slot>start_slot, StakeError::InvalidBlockGap);
+
+        let clock = Clock::get()?;
+
+        require!(end_slot>clock.slot, StakeError::InvalidBlockGap);
+
+        authorize_admin_instruction(
+            &AdminAction::AddRewardStream {
+                index,
+                reward_mint: ctx.accounts.reward_mint.key(),
+                funder: ctx.accounts.funder.key(),
+                budget,
+                start_slot,
+                end_slot,
+            },
+            &ctx.accounts.pgm_info,
+            ctx.accounts.owner.as_ref().map(|owner| owner.key()),
+            ctx.accounts.multisig.as_ref(),
+            ctx.accounts.proposal.as_mut(),
+        )?;
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.funder.to_account_info(),
+            from: ctx.accounts.funder_ata.to_account_info(),
+            mint: ctx.accounts.reward_mint.to_account_info(),
+            to: ctx.accounts.stream_vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, budget, ctx.accounts.reward_mint.decimals)?;
+
+        // the vault is new, so its balance is what arrived after any transfer fee
+        ctx.accounts.stream_vault.reload()?;
+        let received = ctx.accounts.stream_vault.amount;
+
+        // rewards before the stream was added can't be earned
+        let start_slot = start_slot.max(clock.slot);
+        let rate_per_slot = received.checked_div(end_slot-start_slot).ok_or(StakeError::InvalidRewardRate)?;
+
+        require!(rate_per_slot>0, StakeError::InvalidRewardRate);
+
+        reward_streams.streams.push(RewardStream{
+            mint: ctx.accounts.reward_mint.key(),
+            vault: ctx.accounts.stream_vault.key(),
+            funder: ctx.accounts.funder.key(),
+            rate_per_slot,
+            start_slot,
+            end_slot,
+            last_update_slot: start_slot,
+            reward_per_token: 0,
+        });
+
+        emit!(RewardStreamAdded{
+            index,
+            mint: ctx.accounts.reward_mint.key(),
+            funder: ctx.accounts.funder.key(),
+            rate_per_slot,
+            start_slot,
+            end_slot,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+    // Permissionless top-up of a reward stream. Whatever is left of the current budget plus
+    // `amount` is spread over the next `duration` slots (Synthetix StakingRewards style).
+    // Only the stream's funder can slow a running stream down, anyone else (the owner
+    // included) has to keep the rate and the end slot at least where they are.
+    pub fn fund_rewards(ctx:Context<FundRewards>,index:u8,amount:u64,duration:u64)->Result<()>{
+
+        require!(amount>0, StakeError::ZeroAMount);
+        require!(duration>0, StakeError::InvalidBlockGap);
+
+        let clock = Clock::get()?;
+
+        // checkpoint at the old rate first
+        update_reward_streams(&mut ctx.accounts.reward_streams, ctx.accounts.pgm_info.total_staked, clock.slot)?;
+
+        let stream = ctx.accounts.reward_streams.streams.get_mut(index as usize).ok_or(StakeError::InvalidStreamIndex)?;
+
+        require_keys_eq!(ctx.accounts.reward_mint.key(), stream.mint, StakeError::InvalidMint);
+        require_keys_eq!(ctx.accounts.stream_vault.key(), stream.vault, StakeError::MissingStreamAccounts);
+
+        let vault_before = ctx.accounts.stream_vault.amount;
+
+        let cpi_accounts = TransferChecked{
+            authority: ctx.accounts.funder.to_account_info(),
+            from: ctx.accounts.funder_ata.to_account_info(),
+            mint: ctx.accounts.reward_mint.to_account_info(),
+            to: ctx.accounts.stream_vault.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, ctx.accounts.reward_mint.decimals)?;
+
+        // only what arrived after any transfer fee is spread
+        ctx.accounts.stream_vault.reload()?;
+        let received = ctx.accounts.stream_vault.amount.checked_sub(vault_before).ok_or(StakeError::Underflow)?;
+
+        // a stream that hasn't started yet keeps its start slot
+        let from_slot = clock.slot.max(stream.start_slot);
+        let leftover = if stream.end_slot>from_slot {
+            (stream.end_slot - from_slot).checked_mul(stream.rate_per_slot).ok_or(StakeError::Overflow)?
+        } else {
+            0
+        };
+
+        let rate_per_slot = leftover.checked_add(received).ok_or(StakeError::Overflow)? / duration;
+        let end_slot = from_slot.checked_add(duration).ok_or(StakeError::Overflow)?;
+
+        require!(rate_per_slot>0, StakeError::InvalidRewardRate);
+
+        if leftover>0 && ctx.accounts.funder.key()!=stream.funder {
+            require!(rate_per_slot>=stream.rate_per_slot, StakeError::StreamWouldSlowDown);
+            require!(end_slot>=stream.end_slot, StakeError::StreamWouldSlowDown);
+        }
+
+        stream.rate_per_slot = rate_per_slot;
+        stream.start_slot = from_slot;
+        stream.last_update_slot = from_slot;
+        stream.end_slot = end_slot;
+
+        emit!(RewardsFunded{
+            index,
+            funder: ctx.accounts.funder.key(),
+            amount: received,
+            rate_per_slot,
+            end_slot,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+
+        Ok(())
+    }
+
+}
+
+// Accrues every stream's reward per staked token up to `current_slot`.
+// Must run before total_staked changes. While nothing is staked the stream's clock
+// stops: the unpaid part of the budget moves back with the end slot instead of being lost.
+fn update_reward_streams(reward_streams:&mut RewardStreams,total_staked:u64,current_slot:u64)->Result<()>{
+    for stream in reward_streams.streams.iter_mut() {
+        let applicable_slot = current_slot.clamp(stream.start_slot, stream.end_slot);
+
+        if applicable_slot<=stream.last_update_slot {
+            continue;
+        }
+
+        if total_staked==0 {
+            let remaining_slots = stream.end_slot - stream.last_update_slot;
+            let resume_slot = current_slot.max(stream.start_slot);
+
+            stream.end_slot = resume_slot.checked_add(remaining_slots).ok_or(StakeError::Overflow)?;
+            stream.last_update_slot = resume_slot;
+            continue;
+        }
+
+        let slots = (applicable_slot - stream.last_update_slot) as u128;
+        let accrued = slots
+            .checked_mul(stream.rate_per_slot as u128).ok_or(StakeError::Overflow)?
+            .checked_mul(REWARD_PRECISION).ok_or(StakeError::Overflow)?
+            / total_staked as u128;
+
+        stream.reward_per_token = stream.reward_per_token.checked_add(accrued).ok_or(StakeError::Overflow)?;
+        stream.last_update_slot = applicable_slot;
+    }
+
+    Ok(())
+}
+
+// Books what the user earned on each stream since the last checkpoint.
+// Must run before user_info.amount changes.
+fn settle_stream_rewards(reward_streams:&RewardStreams,user_info:&mut UserInfo)->Result<()>{
+    for (i, stream) in reward_streams.streams.iter().enumerate() {
+        let earned = (user_info.amount as u128)
+            .checked_mul(stream.reward_per_token - user_info.stream_reward_per_token_paid[i]).ok_or(StakeError::Overflow)?
+            / REWARD_PRECISION;
+
+        user_info.stream_owed[i] = user_info.stream_owed[i].checked_add(earned as u64).ok_or(StakeError::Overflow)?;
+        user_info.stream_reward_per_token_paid[i] = stream.reward_per_token;
+    }
+
+    Ok(())
+}
+
+// Transfers everything owed on the streams whose accounts were passed, so a frozen or
+// missing token account only holds up its own stream. Returns whether anything was paid.
+fn pay_stream_rewards<'info>(
+    reward_streams:&RewardStreams,
+    user_info:&mut UserInfo,
+    recipient:Pubkey,
+    remaining_accounts:&'info [AccountInfo<'info>],
+    program_auth:&AccountInfo<'info>,
+    auth_bump:u8,
+    token_program:&Interface<'info,TokenInterface>,
+)->Result<bool>{
+
+    require!(remaining_accounts.len().is_multiple_of(3), StakeError::MissingStreamAccounts);
+
+    let bump = &[auth_bump];
+    let signer_seeds = &[&[
+        b"auth".as_ref(),
+        bump,
+    ][..]];
+
+    let mut paid = false;
+
+    for accounts in remaining_accounts.chunks(3) {
+        let (vault, user_token_account, mint) = (&accounts[0], &accounts[1], &accounts[2]);
+
+        let i = reward_streams.streams.iter()
+            .position(|stream| stream.vault==vault.key())
+            .ok_or(StakeError::MissingStreamAccounts)?;
+        let owed = user_info.stream_owed[i];
+
+        if owed==0 {
+            continue;
+        }
+
+        require_keys_eq!(mint.key(), reward_streams.streams[i].mint, StakeError::InvalidMint);
+
+        let destination = InterfaceAccount::<TokenAccount>::try_from(user_token_account)?;
+        require_keys_eq!(destination.owner, recipient, StakeError::Unauthorized);
+
+        let decimals = InterfaceAccount::<Mint>::try_from(mint)?.decimals;
+
+        let cpi_accounts = TransferChecked{
+            authority: program_auth.to_account_info(),
+            from: vault.to_account_info(),
+            mint: mint.to_account_info(),
+            to: user_token_account.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, owed, decimals)?;
+
+        user_info.stream_owed[i] = 0;
+        paid = true;
+
+        let clock = Clock::get()?;
+
+        emit!(StreamRewardsPaid{
+            user: user_info.owner,
+            index: i as u8,
+            amount: owed,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+    }
+
+    Ok(paid)
+}
+
+fn check_allowlist(pgm_info:&ContractInfo,user:&Pubkey,proof:&[[u8;32]])->Result<()>{
+    if let Some(root) = pgm_info.allowlist_root {
+        let leaf = hashv(&[user.as_ref()]).to_bytes();
+        require!(verify_proof(proof, root, leaf), StakeError::NotAllowlisted);
+    }
+
+    Ok(())
+}
+
+// Books rewards earned so far without minting them, so the amount can change
+fn settle_rewards(user_info:&mut UserInfo,contract_info:&ContractInfo,current_slot:u64)->Result<()>{
+    user_info.accrued_rewards = calculate_reward(user_info, contract_info, current_slot)?;
+    user_info.deposit_slot = current_slot;
+    user_info.reward_per_token_paid = reward_per_token(contract_info, current_slot)?;
+    user_info.reward_debt = 0;
+
+    Ok(())
+}
+
+
+// A mint extension that lets someone else move or freeze tokens can drain or lock the vault.
+// Each one has to be explicitly allowed by the policy.
+fn check_mint_extensions(mint:&InterfaceAccount<Mint>,policy:&MintPolicy)->Result<()>{
+
+    if mint.freeze_authority.is_some() {
+        require!(policy.allow_freeze_authority, StakeError::FreezeAuthorityNotAllowed);
+    }
+
+    let mint_info = mint.to_account_info();
+
+    // classic spl-token mints have no extensions
+    if *mint_info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint_info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    for extension in state.get_extension_types()? {
+        match extension {
+            ExtensionType::PermanentDelegate => {
+                require!(policy.allow_permanent_delegate, StakeError::PermanentDelegateNotAllowed);
+            }
+            ExtensionType::TransferHook => {
+                require!(policy.allow_transfer_hook, StakeError::TransferHookNotAllowed);
+            }
+            ExtensionType::TransferFeeConfig => {
+                require!(policy.allow_transfer_fee, StakeError::TransferFeeNotAllowed);
+            }
+            // staked tokens could never leave the vault
+            ExtensionType::NonTransferable => {
+                return err!(StakeError::NonTransferableMint);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn multisig_signer_index(multisig:&Multisig,signer:&Pubkey)->Result<usize>{
+    multisig.signers.iter()
+        .position(|key| key==signer)
+        .ok_or(error!(StakeError::NotMultisigSigner))
+}
+
+fn apply_admin_action<'info>(
+    action:&AdminAction,
+    pgm_info:&mut Account<'info,ContractInfo>,
+    timelock:&mut Account<'info,TimelockQueue>,
+    token_mint:&InterfaceAccount<'info,Mint>,
+    program_auth:&AccountInfo<'info>,
+    token_program:&Interface<'info,TokenInterface>,
+    proposal:Option<Pubkey>,
+)->Result<()>{
+
+    let clock = Clock::get()?;
+
+    match action {
+        AdminAction::QueueChange { change, eta } => {
+            if let ConfigChange::FeeBps { fee_bps } = change {
+                require!(*fee_bps<=MAX_FEE_BPS, StakeError::FeeTooHigh);
+            }
+            require!(*eta>=clock.slot.checked_add(MIN_TIMELOCK_SLOTS).ok_or(StakeError::Overflow)?, StakeError::TimelockTooShort);
+            require!(timelock.pending.len()<MAX_PENDING_CHANGES, StakeError::TimelockQueueFull);
+
+            let id = timelock.next_id;
+            timelock.next_id = id.checked_add(1).ok_or(StakeError::Overflow)?;
+            timelock.pending.push(PendingChange{
+                id,
+                change: change.clone(),
+                eta: *eta,
+            });
+
+            emit!(ConfigChangeQueued{
+                id,
+                change: change.clone(),
+                eta: *eta,
+                slot: clock.slot,
+                timestamp: clock.unix_timestamp,
+            });
+        }
+        AdminAction::CancelChange { id } => {
+            let position = timelock.pending.iter()
+                .position(|pending| pending.id==*id)
+                .ok_or(StakeError::ChangeNotFound)?;
+
+            timelock.pending.remove(position);
+        }
+        AdminAction::SetPaused { paused } => {
+            pgm_info.paused = *paused;
+        }
+        AdminAction::SetDepositCaps { max_total_stake, max_stake_per_wallet } => {
+            pgm_info.max_total_stake = *max_total_stake;
+            pgm_info.max_stake_per_wallet = *max_stake_per_wallet;
+        }
+        AdminAction::SetMinStake { min_stake } => {
+            pgm_info.min_stake = *min_stake;
+        }
+        AdminAction::SetAllowlistRoot { root } => {
+            pgm_info.allowlist_root = *root;
+        }
+        AdminAction::SetSlashing { slasher, destination, max_slash_bps, epoch_cap, appeal_window_slots } => {
+            require!(*max_slash_bps as u128<=BPS_DENOMINATOR, StakeError::SlashTooLarge);
+
+            pgm_info.slasher = *slasher;
+            pgm_info.slash_destination = *destination;
+            pgm_info.max_slash_bps = *max_slash_bps;
+            pgm_info.slash_epoch_cap = *epoch_cap;
+            pgm_info.appeal_window_slots = *appeal_window_slots;
+        }
+        AdminAction::ReleaseMintAuthority { new_authority } => {
+            let bump = &[pgm_info.auth_bump];
+            let signer_seeds = &[&[
+                b"auth".as_ref(),
+                bump,
+            ][..]];
+
+            let cpi_accounts = SetAuthority{
+                account_or_mint: token_mint.to_account_info(),
+                current_authority: program_auth.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+            set_authority(
+                cpi_ctx,
+                anchor_spl::token_interface::spl_token_2022::instruction::AuthorityType::MintTokens,
+                *new_authority
+            )?;
+
+            // the program can't sign mint_to anymore, stakers keep their principal
+            pgm_info.minting_disabled = true;
+        }
+        AdminAction::CreateDistributor { .. }
+        | AdminAction::Clawback { .. }
+        | AdminAction::AddRewardStream { .. }
+        | AdminAction::CancelSlash { .. } => {
+            return err!(StakeError::ActionNeedsOwnInstruction);
+        }
+    }
+
+    emit!(AdminActionExecuted{
+        action: action.clone(),
+        proposal,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Authorizes an admin action that runs through its own instruction: the owner signs until
+// a multisig takes over, after that it needs an approved proposal for exactly this action.
+fn authorize_admin_instruction(
+    action:&AdminAction,
+    pgm_info:&ContractInfo,
+    signer:Option<Pubkey>,
+    multisig:Option<&Account<Multisig>>,
+    proposal:Option<&mut Account<Proposal>>,
+)->Result<()>{
+
+    let clock = Clock::get()?;
+
+    let proposal_key = match pgm_info.admin_multisig {
+        None => {
+            require!(signer==Some(pgm_info.owner), StakeError::Unauthorized);
+            None
+        }
+        Some(admin_multisig) => {
+            let multisig = multisig.ok_or(StakeError::ProposalRequired)?;
+            let proposal = proposal.ok_or(StakeError::ProposalRequired)?;
+
+            require_keys_eq!(multisig.key(), admin_multisig, StakeError::Unauthorized);
+            require_keys_eq!(proposal.multisig, admin_multisig, StakeError::Unauthorized);
+            require!(proposal.action==*action, StakeError::ProposalActionMismatch);
+            require!(!proposal.executed, StakeError::ProposalAlreadyExecuted);
+            require!(proposal.approvals.count_ones()>=multisig.threshold as u32, StakeError::ThresholdNotMet);
+
+            proposal.executed = true;
+            Some(proposal.key())
+        }
+    };
+
+    emit!(AdminActionExecuted{
+        action: action.clone(),
+        proposal: proposal_key,
+        slot: clock.slot,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+fn calculate_reward(
+    user_info:&UserInfo,
+    contract_info: &ContractInfo,
+    current_slot:u64
+)->Result<u64>{
+    // rewards can't be minted anymore, whatever was owed is forfeited
+    if contract_info.minting_disabled {
+        return Ok(0);
+    }
+
+    let earned_per_token = reward_per_token(contract_info, current_slot)?
+        .checked_sub(user_info.reward_per_token_paid).ok_or(StakeError::Underflow)?;
+    let earned = (user_info.amount as u128).checked_mul(earned_per_token).ok_or(StakeError::Overflow)?;
+
+    let result = u64::try_from(earned).map_err(|_| StakeError::Overflow)?
+    .checked_sub(user_info.reward_debt).ok_or(StakeError::Underflow)?
+        .checked_add(user_info.accrued_rewards).ok_or(StakeError::Overflow)?;
+
+    Ok(result)
+}
+
+// Rewards one staked token has earned since the pool was set up. Nothing accrues past
+// end_slot, and rate or end slot changes checkpoint it so they only apply from then on.
+fn reward_per_token(contract_info:&ContractInfo,current_slot:u64)->Result<u128>{
+    let slots = current_slot.min(contract_info.end_slot).saturating_sub(contract_info.reward_index_slot);
+
+    (slots as u128)
+        .checked_mul(contract_info.reward_rate_per_token_per_slot as u128)
+        .and_then(|accrued| accrued.checked_add(contract_info.reward_per_token_stored))
+        .ok_or(error!(StakeError::Overflow))
+}
+
+fn checkpoint_reward_index(contract_info:&mut ContractInfo,current_slot:u64)->Result<()>{
+    contract_info.reward_per_token_stored = reward_per_token(contract_info, current_slot)?;
+    // slots after an expired end_slot never accrue, even if the end is moved out later
+    contract_info.reward_index_slot = contract_info.reward_index_slot.max(current_slot);
+
+    Ok(())
+}
+
+fn protocol_fee(amount:u64,fee_bps:u16)->Result<u64>{
+    let fee = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(StakeError::Overflow)?
+        / BPS_DENOMINATOR;
+
+    Ok(fee as u64)
+}
+
+fn mint_reward<'info>(
+    amount:u64,
+    mint: &InterfaceAccount<'info,Mint>,
+    to: &InterfaceAccount<'info,TokenAccount>,
+    treasury: &InterfaceAccount<'info,TokenAccount>,
+    authority:&AccountInfo<'info>,
+    program_info:&mut Account<'info,ContractInfo>,
+    token_program: &Interface<'info,TokenInterface>,
+)->Result<()>{
+
+    msg!("mint_reward called with amount: {}", amount);
+    msg!("Minting to account: {}", to.key());
+    msg!("Using authority: {}", authority.key());
+
+//  Cpi Call karni hai - Pda has authority so have to pass the Seeds 
+    let bump = &[program_info.auth_bump];
+    let signer_seeds = &[&[
+        b"auth".as_ref(),
+        bump,
+    ][..]];
+
+    // protocol fee goes to the treasury, the rest to the user
+    let fee = protocol_fee(amount, program_info.fee_bps)?;
+    let user_amount = amount.checked_sub(fee).ok_or(StakeError::Underflow)?;
+
+    let cpi_accounts = MintTo{
+        authority:authority.to_account_info(),
+        mint:mint.to_account_info(),
+        to:to.to_account_info()
+    };
+
+    let cpi_program = token_program.to_account_info();
+
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
+
+    msg!("About to call mint_to with amount: {}", user_amount);
+    mint_to(cpi_ctx, user_amount)?;
+    msg!("mint_to completed successfully");
+
+    if fee>0 {
+        let cpi_accounts = MintTo{
+            authority:authority.to_account_info(),
+            mint:mint.to_account_info(),
+            to:treasury.to_account_info()
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        mint_to(cpi_ctx, fee)?;
+
+        program_info.total_fees_collected = program_info.total_fees_collected.checked_add(fee).ok_or(StakeError::Overflow)?;
+
+        let clock = Clock::get()?;
+
+        emit!(FeeCollected{
+            treasury: treasury.key(),
+            amount: fee,
+            total_fees_collected: program_info.total_fees_collected,
+            slot: clock.slot,
+            timestamp: clock.unix_timestamp,
+        });
+    }
+
+
+    Ok(())
+}
+
+fn bitmap_len(max_num_nodes:u64)->usize{
+    max_num_nodes.div_ceil(8) as usize
+}
+
+fn is_claimed(claim_bitmap:&ClaimBitmap,index:u64)->bool{
+    claim_bitmap.bits[(index/8) as usize] & (1<<(index%8)) != 0
+}
+
+fn set_claimed(claim_bitmap:&mut ClaimBitmap,index:u64){
+    claim_bitmap.bits[(index/8) as usize] |= 1<<(index%8);
+}
+
+// Sorted-pair merkle proof, same layout as the off-chain tree builder
+fn verify_proof(proof:&[[u8;32]],root:[u8;32],leaf:[u8;32])->bool{
+    let mut computed = leaf;
+
+    for node in proof {
+        computed = if computed <= *node {
+            hashv(&[&computed, node]).to_bytes()
+        } else {
+            hashv(&[node, &computed]).to_bytes()
+        };
+    }
+
+    computed == root
+}
+
+#[account]
+#[cfg_attr(test, derive(Default))]
+pub struct ContractInfo{
+    pub owner:Pubkey,
+    pub start_slot:u64,
+    pub end_slot:u64,
+    pub token_mint:Pubkey,
+    pub reward_rate_per_token_per_slot:u64,
+    // rewards per staked token up to reward_index_slot, see `reward_per_token`
+    pub reward_per_token_stored:u128,
+    pub reward_index_slot:u64,
+    pub auth_bump:u8,
+    pub vault_bump:u8,
+    pub paused:bool,
+    // once set, admin actions need M-of-N approval through proposals
+    pub admin_multisig:Option<Pubkey>,
+    pub fee_bps:u16, // cut of every reward minted to the treasury
+    pub treasury:Pubkey,
+    pub total_fees_collected:u64,
+    pub total_staked:u64,
+    pub max_total_stake:u64,
+    pub max_stake_per_wallet:u64,
+    // un_stake always withdraws everything, so only the stake side needs a minimum
+    pub min_stake:u64,
+    // merkle root of sha256(wallet) leaves, None = open to everyone
+    pub allowlist_root:Option<[u8;32]>,
+    pub mint_policy:MintPolicy,
+    // set once the mint authority is released, rewards stop accruing from then on
+    pub minting_disabled:bool,
+    // Token-2022 receipts, transfers run through the receipt hook
+    pub receipt_mint:Pubkey,
+    pub receipt_hook:Pubkey,
+    pub hook_authority:Pubkey, // receipt hook PDA allowed to call transfer_position
+    // bonded operators, None = slashing disabled
+    pub slasher:Option<Pubkey>,
+    pub slash_destination:Pubkey, // token account of the staking mint
+    pub max_slash_bps:u16, // of the position, per slash
+    pub slash_epoch_cap:u64, // across all users
+    pub appeal_window_slots:u64, // how long a queued slash can be cancelled before it executes
+    pub slash_epoch:u64,
+    pub slashed_this_epoch:u64,
+}
+
+// Which risky Token-2022 mint features the pool was set up to accept
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MintPolicy{
+    pub allow_freeze_authority:bool,
+    pub allow_permanent_delegate:bool,
+    pub allow_transfer_hook:bool,
+    pub allow_transfer_fee:bool, // stake credits what arrives, see `stake`
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum AdminAction{
+    // rate and end slot changes only go through the timelock
+    QueueChange { change:ConfigChange, eta:u64 },
+    CancelChange { id:u64 },
+    SetPaused { paused:bool },
+    // stops reward minting for good, None also closes the mint supply
+    ReleaseMintAuthority { new_authority:Option<Pubkey> },
+    SetDepositCaps { max_total_stake:u64, max_stake_per_wallet:u64 },
+    SetMinStake { min_stake:u64 },
+    // None disables gating
+    SetAllowlistRoot { root:Option<[u8;32]> },
+    // slasher None disables slashing
+    SetSlashing { slasher:Option<Pubkey>, destination:Pubkey, max_slash_bps:u16, epoch_cap:u64, appeal_window_slots:u64 },
+    // these need more accounts, they run through their own instruction
+    CreateDistributor { season:u64, root:[u8;32], max_total_claim:u64, max_num_nodes:u64, clawback_slot:u64 },
+    Clawback { season:u64, destination:Pubkey },
+    CancelSlash { user:Pubkey },
+    AddRewardStream { index:u8, reward_mint:Pubkey, funder:Pubkey, budget:u64, start_slot:u64, end_slot:u64 },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigChange{
+    RewardRate { reward_rate:u64 },
+    EndSlot { end_slot:u64 },
+    FeeBps { fee_bps:u16 },
+    // must be a token account of the staking mint, checked when executed
+    Treasury { treasury:Pubkey },
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PendingChange{
+    pub id:u64,
+    pub change:ConfigChange,
+    pub eta:u64, // slot after which the change can be executed
+}
+
+// Queue of pending ContractInfo changes, readable by clients
+#[account]
+pub struct TimelockQueue{
+    pub next_id:u64,
+    pub pending:Vec<PendingChange>,
+    pub bump:u8,
+}
+
+#[account]
+pub struct Multisig{
+    pub signers:Vec<Pubkey>,
+    pub threshold:u8,
+    pub proposal_count:u64,
+    pub bump:u8,
+}
+
+#[account]
+pub struct Proposal{
+    pub multisig:Pubkey,
+    pub index:u64,
+    pub proposer:Pubkey,
+    pub action:AdminAction,
+    pub approvals:u16, // bit i set = multisig.signers[i] approved
+    pub executed:bool,
+    pub bump:u8,
+}
+
+#[account]
+#[cfg_attr(test, derive(Default))]
+pub struct UserInfo{
+    pub owner:Pubkey,
+    pub amount:u64,
+    pub deposit_slot:u64,
+    pub reward_per_token_paid:u128, // reward_per_token at the last checkpoint
+    pub reward_debt:u64,
+    pub accrued_rewards:u64, // settled on receipt transfers, paid out with the next reward
+    // per reward stream, indexed like RewardStreams.streams
+    pub stream_reward_per_token_paid:[u128;MAX_REWARD_STREAMS],
+    pub stream_owed:[u64;MAX_REWARD_STREAMS],
+    pub locked_until_slot:u64, // grants only, see `stake_grant`
+    // queued by `slash`, the position is frozen until it's executed or cancelled
+    pub pending_slash_amount:u64,
+    pub pending_slash_reason:u16,
+    pub appeal_until_slot:u64, // the pending slash can't be executed before this
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RewardStream{
+    pub mint:Pubkey,
+    pub vault:Pubkey,
+    pub funder:Pubkey,
+    pub rate_per_slot:u64, // shared by all stakers, not per token
+    pub start_slot:u64,
+    pub end_slot:u64,
+    pub last_update_slot:u64,
+    pub reward_per_token:u128, // scaled by REWARD_PRECISION
+}
+
+#[account]
+pub struct RewardStreams{
+    pub streams:Vec<RewardStream>,
+    pub bump:u8,
+}
+
+// Airdrop for one season, funded up front and claimable until `clawback_slot`
+#[account]
+pub struct MerkleDistributor{
+    pub season:u64,
+    pub root:[u8;32],
+    pub mint:Pubkey,
+    pub max_total_claim:u64,
+    pub max_num_nodes:u64,
+    pub total_claimed:u64,
+    pub num_claimed:u64,
+    pub clawback_slot:u64,
+    pub clawed_back:bool,
+    pub bump:u8,
+    pub vault_bump:u8,
+}
+
+// One bit per leaf index of the distributor, set once the leaf is claimed
+#[account]
+pub struct ClaimBitmap{
+    pub distributor:Pubkey,
+    pub bits:Vec<u8>,
+}
+
+// Intializing Contract 
+// 1) PDA - to store all account config
+// 2) Token min create karna hai
+// 3) PDA to have a authority to sign 
+// 4) PDA created from token that can store all the token - vault
+#[derive(Accounts)]
+pub struct Initialize<'info>{
+    #[account(mut)]
+    pub owner : Signer<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + size_of::<ContractInfo>(),
+        seeds = [b"config"],
+        bump
+    )]
+    pub program_config : Account<'info , ContractInfo>,
+
+    // have to change the token authority to a PDA so that pgm can freely sign it 
+    #[account(
+        mut,
+        mint::authority = owner,
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    // Inside the function we have to make a CPI call to pass the authority to this 
+      /// CHECK: 
+    #[account(
+        seeds = [b"auth"],
+        bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            b"vault", token_mint.key().as_ref()
+        ],
+        bump,
+        token::mint = token_mint,
+        token::authority = program_auth,
+    )]
+    pub program_vault: InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        token::mint = token_mint,
+    )]
+    pub treasury : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"receipt-mint"],
+        bump,
+        mint::decimals = token_mint.decimals,
+        mint::authority = program_auth,
+        mint::token_program = receipt_token_program,
+        extensions::transfer_hook::authority = program_auth,
+        extensions::transfer_hook::program_id = receipt_hook,
+        // lets `slash` burn the receipts of the slashed part
+        extensions::permanent_delegate::delegate = program_auth,
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    /// CHECK: created by the receipt hook
+    #[account(
+        mut,
+        seeds = [b"extra-account-metas", receipt_mint.key().as_ref()],
+        bump,
+        seeds::program = receipt_hook
+    )]
+    pub extra_account_meta_list : UncheckedAccount<'info>,
+
+    /// CHECK: any program implementing the hook
+    #[account(executable)]
+    pub receipt_hook : UncheckedAccount<'info>,
+
+    pub receipt_token_program : Program<'info,Token2022>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 8 + 4 + PENDING_CHANGE_SIZE*MAX_PENDING_CHANGES + 1,
+        seeds = [b"timelock"],
+        bump
+    )]
+    pub timelock : Account<'info,TimelockQueue>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 4 + REWARD_STREAM_SIZE*MAX_REWARD_STREAMS + 1,
+        seeds = [b"reward-streams"],
+        bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+    pub system_program : Program<'info, System>,
+}
+
+
+
+#[derive(Accounts)]
+pub struct Stake<'info>{
+
+    // pays rent for new accounts, a relayer or the user themselves
+    #[account(mut)]
+    pub fee_payer : Signer<'info>,
+
+    pub user : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury,
+        constraint = !program_info.paused @StakeError::Paused
+    )]
+    pub program_info : Account<'info,ContractInfo>,
+
+    #[account(mut)]
+    pub treasury : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        // Risky to use init_if_needed , always pair it with seed and verify user 
+        init_if_needed,
+        payer = fee_payer,
+        space = 8+ size_of::<UserInfo>(),
+        seeds = [
+            b"user-info",
+            user.key().as_ref()
+        ],
+        bump
+
+    )]
+    pub user_info : Account<'info,UserInfo>,
+
+
+    #[account(
+        mut,
+        address = program_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = user,
+        associated_token::mint = token_mint,
+
+    )]
+    pub user_ata : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = program_info.vault_bump,
+    )]
+    pub token_vault : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = program_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = program_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = fee_payer,
+        associated_token::authority = user,
+        associated_token::mint = receipt_mint,
+        associated_token::token_program = receipt_token_program,
+    )]
+    pub user_receipt_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    pub system_program : Program<'info, System>,
+    pub token_program : Interface<'info,TokenInterface>,
+    pub receipt_token_program : Program<'info,Token2022>,
+    pub associated_token_program : Program<'info,AssociatedToken>,
+}
+
+
+#[derive(Accounts)]
+#[instruction(id:u64)]
+pub struct StakeGrant<'info>{
+
+    #[account(mut)]
+    pub payer : Signer<'info>,
+
+    /// CHECK: any wallet, only used as the owner of the grant
+    pub beneficiary : UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = !program_info.paused @StakeError::Paused
+    )]
+    pub program_info : Box<Account<'info,ContractInfo>>,
+
+    // kept apart from the beneficiary's user-info position so the lock only covers the grant
+    #[account(
+        init,
+        payer = payer,
+        space = 8+ size_of::<UserInfo>(),
+        seeds = [
+            b"grant",
+            beneficiary.key().as_ref(),
+            id.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub grant_info : Box<Account<'info,UserInfo>>,
+
+    #[account(
+        address = program_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = payer,
+    )]
+    pub payer_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = program_info.vault_bump,
+    )]
+    pub token_vault : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub system_program : Program<'info, System>,
+    pub token_program : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(id:u64)]
+pub struct ClaimGrant<'info>{
+    #[account(mut)]
+    pub beneficiary : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"grant",
+            beneficiary.key().as_ref(),
+            id.to_le_bytes().as_ref()
+        ],
+        bump,
+        // closed in the handler once the principal is back and no stream rewards are owed
+    )]
+    pub grant_info : Box<Account<'info,UserInfo>>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(mut)]
+    pub treasury : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        mut,
+        associated_token::authority = beneficiary,
+        associated_token::mint = token_mint,
+        associated_token::token_program = token_pgm,
+    )]
+    pub beneficiary_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = pgm_info.vault_bump
+    )]
+    pub vault : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub mint_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub token_pgm : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct StakeFor<'info>{
+
+    #[account(mut)]
+    pub payer : Signer<'info>,
+
+    /// CHECK: any wallet, only used as the owner of the position
+    pub beneficiary : UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = !program_info.paused @StakeError::Paused
+    )]
+    pub program_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8+ size_of::<UserInfo>(),
+        seeds = [
+            b"user-info",
+            beneficiary.key().as_ref()
+        ],
+        bump
+    )]
+    pub user_info : Box<Account<'info,UserInfo>>,
+
+    #[account(
+        address = program_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = payer,
+    )]
+    pub payer_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = program_info.vault_bump,
+    )]
+    pub token_vault : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = program_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = program_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::authority = beneficiary,
+        associated_token::mint = receipt_mint,
+        associated_token::token_program = receipt_token_program,
+    )]
+    pub beneficiary_receipt_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    pub system_program : Program<'info, System>,
+    pub token_program : Interface<'info,TokenInterface>,
+    pub receipt_token_program : Program<'info,Token2022>,
+    pub associated_token_program : Program<'info,AssociatedToken>,
+}
+
+
+#[derive(Accounts)]
+pub struct UnStake<'info>{
+    /// CHECK: 
+    #[account(mut)]
+    pub user:Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user-info",
+            user.key().as_ref()
+        ],
+        bump,
+        // closed in the handler once no stream rewards are owed
+    )]
+    pub user_info : Account<'info, UserInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(mut)]
+    pub treasury : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = user,
+        associated_token::mint = token_mint
+    )]
+    pub user_ata: InterfaceAccount<'info,TokenAccount>,
+
+    // optional reward destination, e.g. a cold wallet or custodian
+    #[account(
+        mut,
+        token::mint = token_mint,
+    )]
+    pub recipient : Option<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = pgm_info.vault_bump
+    )]
+    pub vault : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )] 
+    pub mint_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    #[account(
+        mut,
+        address = pgm_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        mut,
+        associated_token::authority = user,
+        associated_token::mint = receipt_mint,
+        associated_token::token_program = receipt_token_program,
+    )]
+    pub user_receipt_ata : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    pub token_pgm : Interface<'info,TokenInterface>,
+
+    pub receipt_token_program : Program<'info,Token2022>,
+
+    pub ata_program: Program<'info,AssociatedToken>,
+
+    pub system_pgm : Program<'info,System>,
+}
+
+
+#[derive(Accounts)]
+pub struct ClaimPoints<'info>{
+
+    #[account(mut)]
+    pub user : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user-info",
+            user.key().as_ref()
+        ],
+        bump,
+    )]
+    pub user_info: Account<'info,UserInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury,
+        constraint = !pgm_info.paused @StakeError::Paused
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(mut)]
+    pub treasury : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = user,
+        associated_token::mint = token_mint
+    )]
+    pub user_ata : InterfaceAccount<'info,TokenAccount>,
+
+    // optional reward destination, e.g. a cold wallet or custodian
+    #[account(
+        mut,
+        token::mint = token_mint,
+    )]
+    pub recipient : Option<InterfaceAccount<'info,TokenAccount>>,
+
+    /// CHECK: 
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub mint_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub token_pgm : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimPointsRelayed<'info>{
+
+    // pays the transaction fee
+    #[account(mut)]
+    pub relayer : Signer<'info>,
+
+    // only authorises the claim, doesn't need any SOL
+    pub user : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"user-info",
+            user.key().as_ref()
+        ],
+        bump,
+    )]
+    pub user_info: Box<Account<'info,UserInfo>>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury,
+        constraint = !pgm_info.paused @StakeError::Paused
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(mut)]
+    pub treasury : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        associated_token::authority = user,
+        associated_token::mint = token_mint
+    )]
+    pub user_ata : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = relayer,
+    )]
+    pub relayer_ata : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK: 
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub mint_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub token_pgm : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CrankCompound<'info>{
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = treasury @StakeError::InvalidTreasury,
+        constraint = !pgm_info.paused @StakeError::Paused
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(mut)]
+    pub treasury : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = pgm_info.vault_bump
+    )]
+    pub vault : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub mint_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        address = pgm_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub token_pgm : Interface<'info,TokenInterface>,
+
+    pub receipt_token_program : Program<'info,Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct Slash<'info>{
+    pub slasher : Signer<'info>,
+
+    /// CHECK: owner of the position being slashed
+    pub user : UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = pgm_info.slasher == Some(slasher.key()) @StakeError::Unauthorized
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(
+        mut,
+        seeds = [b"user-info", user.key().as_ref()],
+        bump
+    )]
+    pub user_info : Box<Account<'info,UserInfo>>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSlash<'info>{
+    /// CHECK: owner of the position being slashed
+    pub user : UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        // turning slashing off also stops queued slashes from executing
+        constraint = pgm_info.slasher.is_some() @StakeError::Unauthorized
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    #[account(
+        mut,
+        seeds = [b"user-info", user.key().as_ref()],
+        bump
+    )]
+    pub user_info : Box<Account<'info,UserInfo>>,
+
+    #[account(
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"vault",token_mint.key().as_ref()],
+        bump = pgm_info.vault_bump
+    )]
+    pub vault : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        address = pgm_info.slash_destination @StakeError::InvalidSlashDestination,
+        token::mint = token_mint,
+    )]
+    pub slash_destination : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    #[account(
+        mut,
+        address = pgm_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : Box<InterfaceAccount<'info,Mint>>,
+
+    // any receipt account of the user, not only the ATA
+    #[account(
+        mut,
+        token::mint = receipt_mint,
+        token::authority = user,
+        token::token_program = receipt_token_program,
+    )]
+    pub user_receipt_account : Box<InterfaceAccount<'info,TokenAccount>>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+
+    pub receipt_token_program : Program<'info,Token2022>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSlash<'info>{
+    // the owner, or whoever executes the approved proposal once the multisig is set
+    pub authority : Signer<'info>,
+
+    /// CHECK: owner of the slashed position
+    pub user : UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub pgm_info : Box<Account<'info,ContractInfo>>,
+
+    // checked against pgm_info.admin_multisig in the handler
+    pub multisig : Option<Account<'info,Multisig>>,
+
+    #[account(mut)]
+    pub proposal : Option<Account<'info,Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [b"user-info", user.key().as_ref()],
+        bump
+    )]
+    pub user_info : Box<Account<'info,UserInfo>>,
+}
+
+#[derive(Accounts)]
+#[instruction(season:u64, root:[u8;32], max_total_claim:u64, max_num_nodes:u64)]
+pub struct CreateDistributor<'info>{
+    // the owner, or whoever executes the approved proposal once the multisig is set
+    #[account(mut)]
+    pub funder : Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    // checked against pgm_info.admin_multisig in the handler
+    pub multisig : Option<Account<'info,Multisig>>,
+
+    #[account(mut)]
+    pub proposal : Option<Account<'info,Proposal>>,
+
+    #[account(
+        init,
+        payer = funder,
+        space = 8 + size_of::<MerkleDistributor>(),
+        seeds = [b"distributor", season.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub distributor : Account<'info,MerkleDistributor>,
+
+    #[account(
+        init,
+        payer = funder,
+        space = 8 + 32 + 4 + bitmap_len(max_num_nodes),
+        seeds = [b"claim-bitmap", distributor.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap : Account<'info,ClaimBitmap>,
+
+    #[account(
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = funder,
+    )]
+    pub funder_ata : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        init,
+        payer = funder,
+        seeds = [b"distributor-vault", distributor.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = program_auth,
+    )]
+    pub distributor_vault : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+    pub system_program : Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAirdrop<'info>{
+    #[account(mut)]
+    pub claimant : Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"distributor", distributor.season.to_le_bytes().as_ref()],
+        bump = distributor.bump
+    )]
+    pub distributor : Account<'info,MerkleDistributor>,
+
+    #[account(
+        mut,
+        seeds = [b"claim-bitmap", distributor.key().as_ref()],
+        bump
+    )]
+    pub claim_bitmap : Account<'info,ClaimBitmap>,
+
+    #[account(
+        address = distributor.mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"distributor-vault", distributor.key().as_ref()],
+        bump = distributor.vault_bump
+    )]
+    pub distributor_vault : InterfaceAccount<'info,TokenAccount>,
 
     #[account(
-        // Risky to use init_if_needed , always pair it with seed and verify user 
         init_if_needed,
-        payer = user,
-        space = 8+ size_of::<UserInfo>(),
-        seeds = [
-            b"user-info",
-            user.key().as_ref()
-        ],
+        payer = claimant,
+        associated_token::authority = claimant,
+        associated_token::mint = token_mint,
+        associated_token::token_program = token_program,
+    )]
+    pub claimant_ata : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+    pub associated_token_program : Program<'info,AssociatedToken>,
+    pub system_program : Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct Clawback<'info>{
+    // the owner, or whoever executes the approved proposal once the multisig is set
+    pub authority : Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
+        bump
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    // checked against pgm_info.admin_multisig in the handler
+    pub multisig : Option<Account<'info,Multisig>>,
+
+    #[account(mut)]
+    pub proposal : Option<Account<'info,Proposal>>,
+
+    #[account(
+        mut,
+        seeds = [b"distributor", distributor.season.to_le_bytes().as_ref()],
+        bump = distributor.bump
+    )]
+    pub distributor : Account<'info,MerkleDistributor>,
+
+    #[account(
+        address = distributor.mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"distributor-vault", distributor.key().as_ref()],
+        bump = distributor.vault_bump
+    )]
+    pub distributor_vault : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+    )]
+    pub destination : InterfaceAccount<'info,TokenAccount>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+}
+
+// Events - emitted on every state transition so indexers don't have to parse msg! logs
+
+#[derive(Accounts)]
+pub struct ExecuteAdminAction<'info>{
+    pub owner : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = owner @StakeError::Unauthorized,
+        constraint = pgm_info.admin_multisig.is_none() @StakeError::MultisigRequired
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock"],
+        bump = timelock.bump
+    )]
+    pub timelock : Account<'info,TimelockQueue>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(signers:Vec<Pubkey>)]
+pub struct CreateMultisig<'info>{
+    #[account(mut)]
+    pub owner : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        has_one = owner @StakeError::Unauthorized,
+        constraint = pgm_info.admin_multisig.is_none() @StakeError::MultisigRequired
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 4 + 32*MAX_MULTISIG_SIGNERS + 1 + 8 + 1,
+        seeds = [b"multisig"],
+        bump
+    )]
+    pub multisig : Account<'info,Multisig>,
+
+    pub system_program : Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct Propose<'info>{
+    #[account(mut)]
+    pub proposer : Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"multisig"],
+        bump = multisig.bump
+    )]
+    pub multisig : Account<'info,Multisig>,
+
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + size_of::<Proposal>(),
+        seeds = [b"proposal", multisig.proposal_count.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub proposal : Account<'info,Proposal>,
+
+    pub system_program : Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct Approve<'info>{
+    pub signer : Signer<'info>,
+
+    #[account(
+        seeds = [b"multisig"],
+        bump = multisig.bump
+    )]
+    pub multisig : Account<'info,Multisig>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.index.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        has_one = multisig
+    )]
+    pub proposal : Account<'info,Proposal>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info>{
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump,
+        constraint = pgm_info.admin_multisig == Some(multisig.key()) @StakeError::Unauthorized
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    #[account(
+        seeds = [b"multisig"],
+        bump = multisig.bump
+    )]
+    pub multisig : Account<'info,Multisig>,
+
+    #[account(
+        mut,
+        seeds = [b"proposal", proposal.index.to_le_bytes().as_ref()],
+        bump = proposal.bump,
+        has_one = multisig
+    )]
+    pub proposal : Account<'info,Proposal>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock"],
+        bump = timelock.bump
+    )]
+    pub timelock : Account<'info,TimelockQueue>,
+
+    #[account(
+        mut,
+        address = pgm_info.token_mint @StakeError::InvalidMint
+    )]
+    pub token_mint : InterfaceAccount<'info,Mint>,
+
+    /// CHECK:
+    #[account(
+        seeds = [b"auth"],
+        bump = pgm_info.auth_bump
+    )]
+    pub program_auth : AccountInfo<'info>,
+
+    pub token_program : Interface<'info,TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteChange<'info>{
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
+
+    // only needed for treasury changes
+    pub new_treasury : Option<InterfaceAccount<'info,TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"timelock"],
+        bump = timelock.bump
+    )]
+    pub timelock : Account<'info,TimelockQueue>,
+}
+
+#[derive(Accounts)]
+pub struct OpenPosition<'info>{
+    #[account(mut)]
+    pub user : Signer<'info>,
+
+    #[account(
+        seeds = [b"config"],
         bump
+    )]
+    pub pgm_info : Account<'info,ContractInfo>,
 
+    #[account(
+        init,
+        payer = user,
+        space = 8 + size_of::<UserInfo>(),
+        seeds = [b"user-info", user.key().as_ref()],
+        bump
     )]
     pub user_info : Account<'info,UserInfo>,
 
+    pub system_program : Program<'info,System>,
+}
+
+#[derive(Accounts)]
+pub struct TransferPosition<'info>{
+    pub hook_authority : Signer<'info>,
 
     #[account(
-        mut,
-        address = program_info.token_mint @StakeError::InvalidMint
+        seeds = [b"config"],
+        bump,
+        constraint = pgm_info.hook_authority == hook_authority.key() @StakeError::Unauthorized
     )]
-    pub token_mint : InterfaceAccount<'info,Mint>,
+    pub pgm_info : Account<'info,ContractInfo>,
 
     #[account(
-        mut,
-        associated_token::authority = user,
-        associated_token::mint = token_mint,
+        address = pgm_info.receipt_mint @StakeError::InvalidMint
+    )]
+    pub receipt_mint : InterfaceAccount<'info,Mint>,
 
+    #[account(
+        token::mint = receipt_mint,
     )]
-    pub user_ata : InterfaceAccount<'info,TokenAccount>,
+    pub source : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        token::mint = receipt_mint,
+    )]
+    pub destination : InterfaceAccount<'info,TokenAccount>,
 
     #[account(
         mut,
-        seeds = [b"vault",token_mint.key().as_ref()],
-        bump = program_info.vault_bump,
+        seeds = [b"user-info", source.owner.as_ref()],
+        bump
     )]
-    pub token_vault : InterfaceAccount<'info,TokenAccount>,
+    pub sender_info : Account<'info,UserInfo>,
 
-    /// CHECK:
+    // receiver needs an open position, see `open_position`
     #[account(
-        seeds = [b"auth"],
-        bump = program_info.auth_bump
+        mut,
+        seeds = [b"user-info", destination.owner.as_ref()],
+        bump
     )]
-    pub program_auth : AccountInfo<'info>,
+    pub receiver_info : Account<'info,UserInfo>,
 
-    pub system_program : Program<'info, System>,
-    pub token_program : Interface<'info,TokenInterface>,
-    pub ata_program : Program<'info,AssociatedToken>,
+    #[account(
+        mut,
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
 }
 
-
 #[derive(Accounts)]
-pub struct UnStake<'info>{
-    /// CHECK: 
-    #[account(mut)]
-    pub user:Signer<'info>,
+#[instruction(index:u8)]
+pub struct AddRewardStream<'info>{
+    // approves the stream until a multisig takes over, then a proposal does
+    pub owner : Option<Signer<'info>>,
 
-    #[account(
-        mut,
-        seeds = [
-            b"user-info",
-            user.key().as_ref()
-        ],
-        bump,
-        // close this account and refund the user
-        close = user,
-    )]
-    pub user_info : Account<'info, UserInfo>,
+    #[account(mut)]
+    pub funder : Signer<'info>,
 
     #[account(
         seeds = [b"config"],
-        bump 
+        bump
     )]
     pub pgm_info : Account<'info,ContractInfo>,
 
+    // checked against pgm_info.admin_multisig in the handler
+    pub multisig : Option<Account<'info,Multisig>>,
+
+    #[account(mut)]
+    pub proposal : Option<Account<'info,Proposal>>,
+
     #[account(
         mut,
-        address = pgm_info.token_mint @StakeError::InvalidMint
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
+    )]
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    // streams are paid through the staking mint's token program, see `pay_stream_rewards`
+    #[account(
+        address = pgm_info.token_mint @StakeError::InvalidMint,
+        mint::token_program = token_program,
     )]
     pub token_mint : InterfaceAccount<'info,Mint>,
 
     #[account(
-        mut,
-        associated_token::authority = user,
-        associated_token::mint = token_mint
+        mint::token_program = token_program,
     )]
-    pub user_ata: InterfaceAccount<'info,TokenAccount>,
+    pub reward_mint : InterfaceAccount<'info,Mint>,
 
     #[account(
         mut,
-        seeds = [b"vault",token_mint.key().as_ref()],
-        bump = pgm_info.vault_bump
+        token::mint = reward_mint,
+        token::authority = funder,
     )]
-    pub vault : InterfaceAccount<'info,TokenAccount>,
+    pub funder_ata : InterfaceAccount<'info,TokenAccount>,
+
+    #[account(
+        init,
+        payer = funder,
+        seeds = [b"stream-vault", index.to_le_bytes().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = program_auth,
+        token::token_program = token_program,
+    )]
+    pub stream_vault : InterfaceAccount<'info,TokenAccount>,
 
     /// CHECK:
     #[account(
         seeds = [b"auth"],
         bump = pgm_info.auth_bump
-    )] 
-    pub mint_auth : AccountInfo<'info>,
-
-    pub token_pgm : Interface<'info,TokenInterface>,
-
-    pub ata_program: Program<'info,AssociatedToken>,
+    )]
+    pub program_auth : AccountInfo<'info>,
 
-    pub system_pgm : Program<'info,System>,
+    pub token_program : Interface<'info,TokenInterface>,
+    pub system_program : Program<'info,System>,
 }
 
-
 #[derive(Accounts)]
-pub struct ClaimPoints<'info>{
-
-    #[account(mut)]
-    pub user : Signer<'info>,
-
-    #[account(
-        mut,
-        seeds = [
-            b"user-info",
-            user.key().as_ref()
-        ],
-        bump,
-    )]
-    pub user_info: Account<'info,UserInfo>,
+pub struct FundRewards<'info>{
+    pub funder : Signer<'info>,
 
     #[account(
         seeds = [b"config"],
@@ -458,25 +3512,283 @@ pub struct ClaimPoints<'info>{
 
     #[account(
         mut,
-        address = pgm_info.token_mint @StakeError::InvalidMint
+        seeds = [b"reward-streams"],
+        bump = reward_streams.bump
     )]
-    pub token_mint : InterfaceAccount<'info,Mint>,
+    pub reward_streams : Box<Account<'info,RewardStreams>>,
+
+    pub reward_mint : InterfaceAccount<'info,Mint>,
 
     #[account(
         mut,
-        associated_token::authority = user,
-        associated_token::mint = token_mint
+        token::mint = reward_mint,
+        token::authority = funder,
     )]
-    pub user_ata : InterfaceAccount<'info,TokenAccount>,
+    pub funder_ata : InterfaceAccount<'info,TokenAccount>,
 
-    /// CHECK: 
-    #[account(
-        seeds = [b"auth"],
-        bump = pgm_info.auth_bump
-    )]
-    pub mint_auth : AccountInfo<'info>,
+    #[account(mut)]
+    pub stream_vault : InterfaceAccount<'info,TokenAccount>,
 
-    pub token_pgm : Interface<'info,TokenInterface>,
+    pub token_program : Interface<'info,TokenInterface>,
+}
+
+#[event]
+pub struct Initialized{
+    pub owner:Pubkey,
+    pub token_mint:Pubkey,
+    pub reward_rate_per_token_per_slot:u64,
+    pub start_slot:u64,
+    pub end_slot:u64,
+    pub fee_bps:u16,
+    pub treasury:Pubkey,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct Staked{
+    pub user:Pubkey,
+    pub amount:u64,
+    pub rewards_paid:u64,
+    pub staked_amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct Unstaked{
+    pub user:Pubkey,
+    pub recipient:Pubkey, // owner of the account the rewards went to
+    pub amount:u64,
+    pub rewards_paid:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct RewardsClaimed{
+    pub user:Pubkey,
+    pub recipient:Pubkey, // owner of the account the rewards went to
+    pub amount:u64,
+    pub staked_amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct RewardsCompounded{
+    pub user:Pubkey,
+    pub amount:u64,
+    pub staked_amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct RelayerFeePaid{
+    pub user:Pubkey,
+    pub relayer:Pubkey,
+    pub amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct DistributorCreated{
+    pub distributor:Pubkey,
+    pub season:u64,
+    pub root:[u8;32],
+    pub max_total_claim:u64,
+    pub max_num_nodes:u64,
+    pub clawback_slot:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct AirdropClaimed{
+    pub distributor:Pubkey,
+    pub season:u64,
+    pub index:u64,
+    pub claimant:Pubkey,
+    pub amount:u64,
+    pub total_claimed:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct AirdropClawedBack{
+    pub distributor:Pubkey,
+    pub season:u64,
+    pub amount:u64,
+    pub destination:Pubkey,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct MultisigCreated{
+    pub multisig:Pubkey,
+    pub signers:Vec<Pubkey>,
+    pub threshold:u8,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct ProposalCreated{
+    pub proposal:Pubkey,
+    pub index:u64,
+    pub proposer:Pubkey,
+    pub action:AdminAction,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct ProposalApproved{
+    pub proposal:Pubkey,
+    pub signer:Pubkey,
+    pub approvals:u8,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct AdminActionExecuted{
+    pub action:AdminAction,
+    pub proposal:Option<Pubkey>, // None when executed directly by the owner
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct StakedFor{
+    pub payer:Pubkey,
+    pub beneficiary:Pubkey,
+    pub amount:u64,
+    pub staked_amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct GrantStaked{
+    pub payer:Pubkey,
+    pub beneficiary:Pubkey,
+    pub id:u64,
+    pub amount:u64,
+    pub locked_until_slot:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct GrantClaimed{
+    pub beneficiary:Pubkey,
+    pub id:u64,
+    pub amount:u64, // principal returned, 0 while the grant is locked
+    pub rewards_paid:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct PositionTransferred{
+    pub from:Pubkey,
+    pub to:Pubkey,
+    pub amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct RewardStreamAdded{
+    pub index:u8,
+    pub mint:Pubkey,
+    pub funder:Pubkey,
+    pub rate_per_slot:u64,
+    pub start_slot:u64,
+    pub end_slot:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct RewardsFunded{
+    pub index:u8,
+    pub funder:Pubkey,
+    pub amount:u64,
+    pub rate_per_slot:u64,
+    pub end_slot:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct StreamRewardsPaid{
+    pub user:Pubkey,
+    pub index:u8,
+    pub amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct SlashQueued{
+    pub user:Pubkey,
+    pub slasher:Pubkey,
+    pub amount:u64,
+    pub reason:u16, // operator defined
+    pub appeal_until_slot:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct Slashed{
+    pub user:Pubkey,
+    pub amount:u64,
+    pub reason:u16,
+    pub staked_amount:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct SlashCancelled{
+    pub user:Pubkey,
+    pub amount:u64,
+    pub reason:u16,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct FeeCollected{
+    pub treasury:Pubkey,
+    pub amount:u64,
+    pub total_fees_collected:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct ConfigChangeQueued{
+    pub id:u64,
+    pub change:ConfigChange,
+    pub eta:u64,
+    pub slot:u64,
+    pub timestamp:i64,
+}
+
+#[event]
+pub struct ConfigChangeExecuted{
+    pub id:u64,
+    pub change:ConfigChange,
+    pub slot:u64,
+    pub timestamp:i64,
 }
 
 #[error_code]
@@ -507,4 +3819,236 @@ pub enum StakeError{
 
     #[msg("Staked Amount is 0")]
     NotStaked,
-}
\ No newline at end of file
+
+    #[msg("Distributor can't hold that many leaves")]
+    TooManyNodes,
+
+    #[msg("Leaf index is out of the distributor range")]
+    InvalidDistributorIndex,
+
+    #[msg("Invalid merkle proof")]
+    InvalidProof,
+
+    #[msg("Airdrop already claimed")]
+    AlreadyClaimed,
+
+    #[msg("Claim would exceed the distributor budget")]
+    ExceedsMaxClaim,
+
+    #[msg("Claim window is closed")]
+    ClaimWindowClosed,
+
+    #[msg("Clawback deadline not reached yet")]
+    ClawbackNotStarted,
+
+    #[msg("Distributor already clawed back")]
+    AlreadyClawedBack,
+
+    #[msg("Staking is paused")]
+    Paused,
+
+    #[msg("Admin actions must go through the multisig")]
+    MultisigRequired,
+
+    #[msg("Multisig needs 1 to 10 unique signers")]
+    InvalidMultisigSigners,
+
+    #[msg("Threshold must be between 1 and the number of signers")]
+    InvalidThreshold,
+
+    #[msg("Signer is not part of the multisig")]
+    NotMultisigSigner,
+
+    #[msg("Signer already approved this proposal")]
+    AlreadyApproved,
+
+    #[msg("Proposal was already executed")]
+    ProposalAlreadyExecuted,
+
+    #[msg("Not enough approvals to execute")]
+    ThresholdNotMet,
+
+    #[msg("Eta is earlier than the minimum timelock delay")]
+    TimelockTooShort,
+
+    #[msg("Too many pending changes")]
+    TimelockQueueFull,
+
+    #[msg("No pending change with this id")]
+    ChangeNotFound,
+
+    #[msg("Timelock delay has not elapsed yet")]
+    TimelockNotElapsed,
+
+    #[msg("Fee is above the maximum")]
+    FeeTooHigh,
+
+    #[msg("Treasury account does not match")]
+    InvalidTreasury,
+
+    #[msg("Stake would exceed the per wallet cap")]
+    WalletCapExceeded,
+
+    #[msg("Stake would exceed the pool cap")]
+    PoolCapExceeded,
+
+    #[msg("Position would be below the minimum stake")]
+    BelowMinimumStake,
+
+    #[msg("Wallet is not on the allowlist")]
+    NotAllowlisted,
+
+    #[msg("Mint has a freeze authority")]
+    FreezeAuthorityNotAllowed,
+
+    #[msg("Mint has a permanent delegate")]
+    PermanentDelegateNotAllowed,
+
+    #[msg("Mint has a transfer hook")]
+    TransferHookNotAllowed,
+
+    #[msg("Mint has a transfer fee")]
+    TransferFeeNotAllowed,
+
+    #[msg("Mint is non-transferable")]
+    NonTransferableMint,
+
+    #[msg("Too many reward streams")]
+    TooManyRewardStreams,
+
+    #[msg("Stream index must be the next free slot")]
+    InvalidStreamIndex,
+
+    #[msg("Missing or wrong reward stream accounts")]
+    MissingStreamAccounts,
+
+    #[msg("Only the stream's funder can lower its rate or end slot")]
+    StreamWouldSlowDown,
+
+    #[msg("Lock is longer than allowed")]
+    LockTooLong,
+
+    #[msg("Relayer fee is more than the reward")]
+    RelayerFeeTooHigh,
+
+    #[msg("Crank accounts must be (user_info, receipt ata) pairs of the same owner")]
+    InvalidCrankAccounts,
+
+    #[msg("Too many positions for one crank")]
+    CrankBatchTooLarge,
+
+    #[msg("Slash is more than the allowed share of the position")]
+    SlashTooLarge,
+
+    #[msg("Slash would go over the per epoch cap")]
+    SlashEpochCapExceeded,
+
+    #[msg("Slash is still in its appeal window")]
+    AppealPending,
+
+    #[msg("Position has a pending slash")]
+    SlashPending,
+
+    #[msg("No slash is pending on this position")]
+    NoPendingSlash,
+
+    #[msg("Wrong slash destination")]
+    InvalidSlashDestination,
+
+    #[msg("An approved proposal is needed once the multisig is set")]
+    ProposalRequired,
+
+    #[msg("Proposal is for a different action")]
+    ProposalActionMismatch,
+
+    #[msg("This action runs through its own instruction")]
+    ActionNeedsOwnInstruction,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(rate: u64) -> ContractInfo {
+        ContractInfo {
+            reward_rate_per_token_per_slot: rate,
+            end_slot: 1_000,
+            reward_index_slot: 100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rate_changes_only_apply_from_when_they_execute() {
+        let mut contract_info = pool(10);
+        let mut user_info = UserInfo {
+            amount: 5,
+            ..Default::default()
+        };
+        user_info.reward_per_token_paid = reward_per_token(&contract_info, 100).unwrap();
+
+        // 100 slots at the old rate, then the timelocked change lands
+        checkpoint_reward_index(&mut contract_info, 200).unwrap();
+        contract_info.reward_rate_per_token_per_slot = 1;
+
+        // claiming 100 slots later pays each part at its own rate
+        assert_eq!(
+            calculate_reward(&user_info, &contract_info, 300).unwrap(),
+            5 * 100 * 10 + 5 * 100
+        );
+
+        settle_rewards(&mut user_info, &contract_info, 300).unwrap();
+        assert_eq!(user_info.accrued_rewards, 5 * 100 * 10 + 5 * 100);
+        assert_eq!(
+            calculate_reward(&user_info, &contract_info, 300).unwrap(),
+            user_info.accrued_rewards
+        );
+    }
+
+    #[test]
+    fn extending_an_expired_pool_does_not_pay_the_gap() {
+        let mut contract_info = pool(10);
+        let user_info = UserInfo {
+            amount: 5,
+            reward_per_token_paid: reward_per_token(&contract_info, 100).unwrap(),
+            ..Default::default()
+        };
+
+        checkpoint_reward_index(&mut contract_info, 1_500).unwrap();
+        contract_info.end_slot = 2_000;
+
+        assert_eq!(
+            calculate_reward(&user_info, &contract_info, 1_600).unwrap(),
+            5 * 900 * 10 + 5 * 100 * 10
+        );
+    }
+
+    #[test]
+    fn streams_stop_while_nothing_is_staked() {
+        let mut reward_streams = RewardStreams {
+            streams: vec![RewardStream {
+                mint: Pubkey::default(),
+                vault: Pubkey::default(),
+                funder: Pubkey::default(),
+                rate_per_slot: 10,
+                start_slot: 100,
+                end_slot: 200,
+                last_update_slot: 100,
+                reward_per_token: 0,
+            }],
+            bump: 0,
+        };
+
+        // 50 slots with an empty pool push the end back by 50
+        update_reward_streams(&mut reward_streams, 0, 150).unwrap();
+        assert_eq!(reward_streams.streams[0].end_slot, 250);
+        assert_eq!(reward_streams.streams[0].reward_per_token, 0);
+
+        // once someone stakes the whole budget still gets paid out
+        update_reward_streams(&mut reward_streams, 1, 300).unwrap();
+        assert_eq!(
+            reward_streams.streams[0].reward_per_token,
+            100 * 10 * REWARD_PRECISION
+        );
+    }
+}
@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { StakingTokenContract } from "../target/types/staking_token_contract";
+import { ReceiptHook } from "../target/types/receipt_hook";
 import {
   createAssociatedTokenAccount,
   createMint,
@@ -9,12 +10,20 @@ import {
   getMint,
   mintTo,
   TOKEN_PROGRAM_ID,
+  TOKEN_2022_PROGRAM_ID,
   tokenGroupInitializeGroup,
+  transferCheckedWithTransferHook,
   getAccount,
+  transfer,
 } from "@solana/spl-token";
-import { LAMPORTS_PER_SOL, PublicKey } from "@solana/web3.js";
+import {
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  sendAndConfirmTransaction,
+} from "@solana/web3.js";
 import { publicKey } from "@coral-xyz/anchor/dist/cjs/utils";
 import { assert, expect } from "chai";
+import { createHash } from "crypto";
 
 describe("staking-token-contract", async () => {
   // Configure the client to use the local cluster.b
@@ -26,17 +35,22 @@ describe("staking-token-contract", async () => {
     .stakingTokenContract as Program<StakingTokenContract>;
 
   // console.log("Program", program);
+  const receiptHook = anchor.workspace.receiptHook as Program<ReceiptHook>;
+
   const owner = anchor.web3.Keypair.generate();
   const user1 = anchor.web3.Keypair.generate();
 
   let tokenMint: anchor.web3.PublicKey;
   let user1_ata: anchor.web3.PublicKey;
+  let owner_ata: anchor.web3.PublicKey;
 
   // All PDA's
   let config_pda: anchor.web3.PublicKey;
   let auth_pda: anchor.web3.PublicKey;
   let vault_pda: anchor.web3.PublicKey;
   let userInfo_pda: anchor.web3.PublicKey;
+  let partnerMint: anchor.web3.PublicKey;
+  let user3: anchor.web3.Keypair;
 
   before(async () => {
     const ownerAirdropSignature = await provider.connection.requestAirdrop(
@@ -78,6 +92,23 @@ describe("staking-token-contract", async () => {
       1000 * 10 ** 9
     );
 
+    // owner keeps some tokens to fund airdrops
+    owner_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      tokenMint,
+      owner.publicKey
+    );
+
+    await mintTo(
+      provider.connection,
+      owner,
+      tokenMint,
+      owner_ata,
+      owner,
+      100 * 10 ** 9
+    );
+
     [config_pda] = await anchor.web3.PublicKey.findProgramAddressSync(
       [Buffer.from("config")],
       program.programId
@@ -111,15 +142,38 @@ describe("staking-token-contract", async () => {
   it("Is initialized!", async () => {
     const rewardPerSlot = new anchor.BN(1);
     const startSlot = new anchor.BN(0);
-    const endSlot = new anchor.BN(1000);
+    // rewards stop accruing at end_slot, keep it well past the end of the suite
+    const endSlot = new anchor.BN(1_000_000);
+    const feeBps = 500;
+    const maxTotalStake = new anchor.BN(1000 * 10 ** 9);
+    const maxStakePerWallet = new anchor.BN(2 * 10 ** 9);
+    const minStake = new anchor.BN(10 ** 6);
+    const mintPolicy = {
+      allowFreezeAuthority: false,
+      allowPermanentDelegate: false,
+      allowTransferHook: false,
+      allowTransferFee: false,
+    };
 
     // Create a token Mint from the token Program
 
+    // the owner's ata doubles as the fee treasury
     const tx = await program.methods
-      .initialize(rewardPerSlot, startSlot, endSlot)
+      .initialize(
+        rewardPerSlot,
+        startSlot,
+        endSlot,
+        feeBps,
+        maxTotalStake,
+        maxStakePerWallet,
+        minStake,
+        mintPolicy
+      )
       .accounts({
         owner: owner.publicKey,
         tokenMint: tokenMint,
+        treasury: owner_ata,
+        receiptHook: receiptHook.programId,
         tokenProgram: TOKEN_PROGRAM_ID,
       })
       .signers([owner])
@@ -144,8 +198,9 @@ describe("staking-token-contract", async () => {
     console.log("userTokenBalance", userTokenAccountBefore.amount);
 
     const tx = await program.methods
-      .stake(stakeAmount)
+      .stake(stakeAmount, [])
       .accounts({
+        feePayer: user1.publicKey,
         user: user1.publicKey,
         tokenMint: tokenMint,
         tokenProgram: TOKEN_PROGRAM_ID,
@@ -172,6 +227,24 @@ describe("staking-token-contract", async () => {
     );
   });
 
+  it("rejects stakes above the per wallet cap", async () => {
+    try {
+      await program.methods
+        .stake(new anchor.BN(2 * 10 ** 9), [])
+        .accounts({
+          feePayer: user1.publicKey,
+          user: user1.publicKey,
+          tokenMint: tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user1])
+        .rpc();
+      assert.fail("stake above the wallet cap should fail");
+    } catch (err) {
+      assert.include(err.toString(), "WalletCapExceeded");
+    }
+  });
+
   it("claim_points", async () => {
     const accountBalanceBeforeClaim = (
       await getAccount(provider.connection, user1_ata)
@@ -179,6 +252,9 @@ describe("staking-token-contract", async () => {
 
     console.log("Balance before claiming:", Number(accountBalanceBeforeClaim));
 
+    const treasuryBefore = (await getAccount(provider.connection, owner_ata))
+      .amount;
+
     const stakedAmount = await provider.connection.getAccountInfo(userInfo_pda);
 
     console.log("stakedAmount", stakedAmount);
@@ -202,6 +278,16 @@ describe("staking-token-contract", async () => {
       Number(accountBalanceAfterClaim),
       Number(accountBalanceBeforeClaim)
     );
+
+    const treasuryAfter = (await getAccount(provider.connection, owner_ata))
+      .amount;
+    const config = await program.account.contractInfo.fetch(config_pda);
+
+    assert.isAbove(Number(treasuryAfter), Number(treasuryBefore));
+    assert.equal(
+      config.totalFeesCollected.toString(),
+      (treasuryAfter - treasuryBefore).toString()
+    );
   });
 
   it("Is Unstaking", async () => {
@@ -239,4 +325,1141 @@ describe("staking-token-contract", async () => {
 
     assert.equal(vault.amount, BigInt(0));
   });
+
+  it("claims a season airdrop with a merkle proof", async () => {
+    const season = new anchor.BN(1);
+    const index = new anchor.BN(0);
+    const amount = new anchor.BN(5 * 10 ** 9);
+
+    // single leaf tree, the root is the leaf itself and the proof is empty
+    const leaf = createHash("sha256")
+      .update(
+        Buffer.concat([
+          index.toArrayLike(Buffer, "le", 8),
+          user1.publicKey.toBuffer(),
+          amount.toArrayLike(Buffer, "le", 8),
+        ])
+      )
+      .digest();
+
+    const [distributor_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("distributor"), season.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const currentSlot = await provider.connection.getSlot();
+
+    await program.methods
+      .createDistributor(
+        season,
+        Array.from(leaf),
+        amount,
+        new anchor.BN(1),
+        new anchor.BN(currentSlot + 1000)
+      )
+      .accounts({
+        funder: owner.publicKey,
+        tokenMint: tokenMint,
+        funderAta: owner_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    const balanceBefore = (await getAccount(provider.connection, user1_ata))
+      .amount;
+
+    await program.methods
+      .claimAirdrop(index, amount, [])
+      .accountsPartial({
+        claimant: user1.publicKey,
+        distributor: distributor_pda,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user1])
+      .rpc();
+
+    const balanceAfter = (await getAccount(provider.connection, user1_ata))
+      .amount;
+
+    assert.equal(
+      (balanceAfter - balanceBefore).toString(),
+      amount.toString()
+    );
+  });
+
+  it("owner pauses staking", async () => {
+    await program.methods
+      .executeAdminAction({ setPaused: { paused: true } })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    try {
+      await program.methods
+        .stake(new anchor.BN(10 ** 9), [])
+        .accounts({
+          feePayer: user1.publicKey,
+          user: user1.publicKey,
+          tokenMint: tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user1])
+        .rpc();
+      assert.fail("staking while paused should fail");
+    } catch (err) {
+      assert.include(err.toString(), "Paused");
+    }
+
+    await program.methods
+      .executeAdminAction({ setPaused: { paused: false } })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+  });
+
+  it("queues a timelocked rate change", async () => {
+    const [timelock_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("timelock")],
+      program.programId
+    );
+
+    const currentSlot = await provider.connection.getSlot();
+
+    await program.methods
+      .executeAdminAction({
+        queueChange: {
+          change: { rewardRate: { rewardRate: new anchor.BN(1) } },
+          eta: new anchor.BN(currentSlot + 216_000 + 100),
+        },
+      })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    let timelock = await program.account.timelockQueue.fetch(timelock_pda);
+    assert.equal(timelock.pending.length, 1);
+
+    const id = timelock.pending[0].id;
+
+    try {
+      await program.methods.executeChange(id).rpc();
+      assert.fail("executing before the eta should fail");
+    } catch (err) {
+      assert.include(err.toString(), "TimelockNotElapsed");
+    }
+
+    await program.methods
+      .executeAdminAction({ cancelChange: { id } })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    timelock = await program.account.timelockQueue.fetch(timelock_pda);
+    assert.equal(timelock.pending.length, 0);
+  });
+
+  it("gates new positions behind the allowlist", async () => {
+    const setRoot = (root: number[] | null) =>
+      program.methods
+        .executeAdminAction({ setAllowlistRoot: { root } })
+        .accounts({
+          owner: owner.publicKey,
+          tokenMint: tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+    // single leaf trees, the root is the leaf and the proof is empty
+    const leaf = (wallet: anchor.web3.PublicKey) =>
+      Array.from(createHash("sha256").update(wallet.toBuffer()).digest());
+
+    const stake = () =>
+      program.methods
+        .stake(new anchor.BN(10 ** 9), [])
+        .accounts({
+          feePayer: user1.publicKey,
+          user: user1.publicKey,
+          tokenMint: tokenMint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user1])
+        .rpc();
+
+    await setRoot(leaf(owner.publicKey));
+
+    try {
+      await stake();
+      assert.fail("staking without being allowlisted should fail");
+    } catch (err) {
+      assert.include(err.toString(), "NotAllowlisted");
+    }
+
+    await setRoot(leaf(user1.publicKey));
+    await stake();
+
+    const userInfo = await program.account.userInfo.fetch(userInfo_pda);
+    assert.isTrue(userInfo.owner.equals(user1.publicKey));
+
+    await setRoot(null);
+  });
+
+  it("moves the staked position along with the receipts", async () => {
+    const user2 = anchor.web3.Keypair.generate();
+
+    const airdropSignature = await provider.connection.requestAirdrop(
+      user2.publicKey,
+      anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(airdropSignature);
+
+    const [receipt_mint] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt-mint")],
+      program.programId
+    );
+
+    const [user2Info_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("user-info"), user2.publicKey.toBuffer()],
+      program.programId
+    );
+
+    // the receiver needs a UserInfo for the hook to credit
+    await program.methods
+      .openPosition([])
+      .accounts({ user: user2.publicKey })
+      .signers([user2])
+      .rpc();
+
+    const user1_receipt_ata = getAssociatedTokenAddressSync(
+      receipt_mint,
+      user1.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const user2_receipt_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      user2,
+      receipt_mint,
+      user2.publicKey,
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const before = await program.account.userInfo.fetch(userInfo_pda);
+    const amount = BigInt(before.amount.toString()) / BigInt(2);
+
+    await transferCheckedWithTransferHook(
+      provider.connection,
+      user1,
+      user1_receipt_ata,
+      receipt_mint,
+      user2_receipt_ata,
+      user1,
+      amount,
+      9,
+      [],
+      undefined,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    const sender = await program.account.userInfo.fetch(userInfo_pda);
+    const receiver = await program.account.userInfo.fetch(user2Info_pda);
+
+    assert.equal(
+      sender.amount.toString(),
+      (BigInt(before.amount.toString()) - amount).toString()
+    );
+    assert.equal(receiver.amount.toString(), amount.toString());
+  });
+
+  it("pays partner reward streams on claim", async () => {
+    partnerMint = await createMint(
+      provider.connection,
+      owner,
+      owner.publicKey,
+      null,
+      6
+    );
+
+    const funder_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      partnerMint,
+      owner.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      owner,
+      partnerMint,
+      funder_ata,
+      owner,
+      1_000_000 * 10 ** 6
+    );
+
+    const user1_partner_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      user1,
+      partnerMint,
+      user1.publicKey
+    );
+
+    const index = 0;
+    const [stream_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stream-vault"), Buffer.from([index])],
+      program.programId
+    );
+
+    const currentSlot = await provider.connection.getSlot();
+
+    await program.methods
+      .addRewardStream(
+        index,
+        new anchor.BN(1_000_000 * 10 ** 6),
+        new anchor.BN(currentSlot),
+        new anchor.BN(currentSlot + 1000)
+      )
+      .accounts({
+        owner: owner.publicKey,
+        funder: owner.publicKey,
+        tokenMint: tokenMint,
+        rewardMint: partnerMint,
+        funderAta: funder_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    // let a few slots pass so the stream accrues
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    await program.methods
+      .claimPoints()
+      .accounts({
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+        user: user1.publicKey,
+      })
+      .remainingAccounts([
+        { pubkey: stream_vault, isSigner: false, isWritable: true },
+        { pubkey: user1_partner_ata, isSigner: false, isWritable: true },
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+      ])
+      .signers([user1])
+      .rpc();
+
+    const partnerBalance = (
+      await getAccount(provider.connection, user1_partner_ata)
+    ).amount;
+
+    assert.isAbove(Number(partnerBalance), 0);
+  });
+
+  it("tops up a reward stream without slowing it down", async () => {
+    const [reward_streams_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("reward-streams")],
+      program.programId
+    );
+    const [stream_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stream-vault"), Buffer.from([0])],
+      program.programId
+    );
+
+    const user1_partner_ata = getAssociatedTokenAddressSync(
+      partnerMint,
+      user1.publicKey
+    );
+    const amount = (await getAccount(provider.connection, user1_partner_ata))
+      .amount;
+
+    const before = (
+      await program.account.rewardStreams.fetch(reward_streams_pda)
+    ).streams[0];
+
+    const fundRewards = (
+      funder: anchor.web3.Keypair,
+      funderAta: anchor.web3.PublicKey,
+      amount: anchor.BN
+    ) =>
+      program.methods
+        .fundRewards(0, amount, new anchor.BN(5000))
+        .accounts({
+          funder: funder.publicKey,
+          rewardMint: partnerMint,
+          funderAta: funderAta,
+          streamVault: stream_vault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([funder])
+        .rpc();
+
+    // user1 recycles the partner rewards it just claimed, but spreading them over
+    // 5000 slots would slow the stream down
+    try {
+      await fundRewards(user1, user1_partner_ata, new anchor.BN(amount.toString()));
+      assert.fail("stretching someone else's stream should fail");
+    } catch (err) {
+      assert.include(err.toString(), "StreamWouldSlowDown");
+    }
+
+    // the stream's funder can re-spread it
+    const funder_ata = getAssociatedTokenAddressSync(
+      partnerMint,
+      owner.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      owner,
+      partnerMint,
+      funder_ata,
+      owner,
+      1_000 * 10 ** 6
+    );
+    await fundRewards(owner, funder_ata, new anchor.BN(1_000 * 10 ** 6));
+
+    const after = (
+      await program.account.rewardStreams.fetch(reward_streams_pda)
+    ).streams[0];
+
+    assert.isAbove(after.endSlot.toNumber(), before.endSlot.toNumber());
+    assert.equal(
+      after.endSlot.sub(after.startSlot).toNumber(),
+      5000
+    );
+  });
+
+  it("sends claimed rewards to another wallet", async () => {
+    const coldWallet = anchor.web3.Keypair.generate();
+
+    const cold_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      tokenMint,
+      coldWallet.publicKey
+    );
+    const cold_partner_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      partnerMint,
+      coldWallet.publicKey
+    );
+    const [stream_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stream-vault"), Buffer.from([0])],
+      program.programId
+    );
+
+    const userBalanceBefore = (await getAccount(provider.connection, user1_ata))
+      .amount;
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    await program.methods
+      .claimPoints()
+      .accounts({
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+        user: user1.publicKey,
+        recipient: cold_ata,
+      })
+      .remainingAccounts([
+        { pubkey: stream_vault, isSigner: false, isWritable: true },
+        { pubkey: cold_partner_ata, isSigner: false, isWritable: true },
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+      ])
+      .signers([user1])
+      .rpc();
+
+    const coldBalance = (await getAccount(provider.connection, cold_ata)).amount;
+    const userBalanceAfter = (await getAccount(provider.connection, user1_ata))
+      .amount;
+
+    assert.isAbove(Number(coldBalance), 0);
+    assert.equal(userBalanceAfter, userBalanceBefore);
+  });
+
+  it("stakes and grants on behalf of another wallet", async () => {
+    const contributor = anchor.web3.Keypair.generate();
+
+    const contributor_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      tokenMint,
+      contributor.publicKey
+    );
+
+    const [contributor_info] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("user-info"), contributor.publicKey.toBuffer()],
+      program.programId
+    );
+    const grantId = new anchor.BN(0);
+    const [grant_info] = anchor.web3.PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("grant"),
+        contributor.publicKey.toBuffer(),
+        grantId.toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    );
+
+    // user1 pays, both positions belong to the contributor
+    await program.methods
+      .stakeFor(new anchor.BN(10 ** 9), [])
+      .accounts({
+        payer: user1.publicKey,
+        beneficiary: contributor.publicKey,
+        tokenMint: tokenMint,
+        payerAta: user1_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user1])
+      .rpc();
+
+    const lockUntil = (await provider.connection.getSlot()) + 100_000;
+
+    // the lock is the payer's call, the contributor doesn't sign
+    await program.methods
+      .stakeGrant(grantId, new anchor.BN(10 ** 9), new anchor.BN(lockUntil), [])
+      .accounts({
+        payer: user1.publicKey,
+        beneficiary: contributor.publicKey,
+        tokenMint: tokenMint,
+        payerAta: user1_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([user1])
+      .rpc();
+
+    const info = await program.account.userInfo.fetch(contributor_info);
+    assert.equal(info.owner.toBase58(), contributor.publicKey.toBase58());
+    assert.equal(info.amount.toNumber(), 10 ** 9);
+    assert.equal(info.lockedUntilSlot.toNumber(), 0);
+
+    let grant = await program.account.userInfo.fetch(grant_info);
+    assert.equal(grant.amount.toNumber(), 10 ** 9);
+    assert.equal(grant.lockedUntilSlot.toNumber(), lockUntil);
+
+    // the grant's lock doesn't hold up the contributor's own position
+    await program.methods
+      .unStake()
+      .accounts({
+        user: contributor.publicKey,
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .signers([contributor])
+      .rpc();
+
+    const balance = (await getAccount(provider.connection, contributor_ata))
+      .amount;
+    assert.isTrue(balance >= BigInt(10 ** 9));
+
+    // while locked, claiming the grant only pays its rewards
+    await program.methods
+      .claimGrant(grantId)
+      .accounts({
+        beneficiary: contributor.publicKey,
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .signers([contributor])
+      .rpc();
+
+    grant = await program.account.userInfo.fetch(grant_info);
+    assert.equal(grant.amount.toNumber(), 10 ** 9);
+    assert.isTrue(
+      (await getAccount(provider.connection, contributor_ata)).amount > balance
+    );
+  });
+
+  it("lets a relayer pay for a claim and take a fee", async () => {
+    const [stream_vault] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stream-vault"), Buffer.from([0])],
+      program.programId
+    );
+    const user1_partner_ata = getAssociatedTokenAddressSync(
+      partnerMint,
+      user1.publicKey
+    );
+
+    const userLamportsBefore = await provider.connection.getBalance(
+      user1.publicKey
+    );
+    const relayerBalanceBefore = (
+      await getAccount(provider.connection, owner_ata)
+    ).amount;
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    // owner relays, user1 only signs as authority
+    const tx = await program.methods
+      .claimPointsRelayed(new anchor.BN(1000))
+      .accounts({
+        relayer: owner.publicKey,
+        user: user1.publicKey,
+        tokenMint: tokenMint,
+        relayerAta: owner_ata,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: stream_vault, isSigner: false, isWritable: true },
+        { pubkey: user1_partner_ata, isSigner: false, isWritable: true },
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+      ])
+      .transaction();
+    tx.feePayer = owner.publicKey;
+
+    await sendAndConfirmTransaction(provider.connection, tx, [owner, user1]);
+
+    const userLamportsAfter = await provider.connection.getBalance(
+      user1.publicKey
+    );
+    const relayerBalanceAfter = (
+      await getAccount(provider.connection, owner_ata)
+    ).amount;
+
+    assert.equal(userLamportsAfter, userLamportsBefore);
+    assert.isTrue(relayerBalanceAfter > relayerBalanceBefore);
+  });
+
+  it("compounds rewards for a batch of positions", async () => {
+    const [receipt_mint] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt-mint")],
+      program.programId
+    );
+    const user1_receipt_ata = getAssociatedTokenAddressSync(
+      receipt_mint,
+      user1.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+
+    // a few slots of rewards would push user1 over the 2 token wallet cap
+    await program.methods
+      .executeAdminAction({
+        setDepositCaps: {
+          maxTotalStake: new anchor.BN(10 ** 6 * 10 ** 9),
+          maxStakePerWallet: new anchor.BN(10 ** 6 * 10 ** 9),
+        },
+      })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    const before = await program.account.userInfo.fetch(userInfo_pda);
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    // anyone can crank, the provider wallet does it here
+    await program.methods
+      .crankCompound()
+      .accounts({
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: userInfo_pda, isSigner: false, isWritable: true },
+        { pubkey: user1_receipt_ata, isSigner: false, isWritable: true },
+      ])
+      .rpc();
+
+    const after = await program.account.userInfo.fetch(userInfo_pda);
+    const receipts = (
+      await getAccount(
+        provider.connection,
+        user1_receipt_ata,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      )
+    ).amount;
+
+    assert.isTrue(after.amount.gt(before.amount));
+    assert.equal(receipts.toString(), after.amount.toString());
+  });
+
+  it("queues a slash and executes it after the appeal window", async () => {
+    const appealWindow = 10;
+
+    await program.methods
+      .executeAdminAction({
+        setSlashing: {
+          slasher: owner.publicKey,
+          destination: owner_ata,
+          maxSlashBps: 1_000,
+          epochCap: new anchor.BN(10 ** 9),
+          appealWindowSlots: new anchor.BN(appealWindow),
+        },
+      })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    const [receipt_mint] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("receipt-mint")],
+      program.programId
+    );
+    const user1_receipt_ata = getAssociatedTokenAddressSync(
+      receipt_mint,
+      user1.publicKey,
+      false,
+      TOKEN_2022_PROGRAM_ID
+    );
+    const receipts = async () =>
+      (
+        await getAccount(
+          provider.connection,
+          user1_receipt_ata,
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ).amount;
+
+    const slash = (amount: anchor.BN) =>
+      program.methods
+        .slash(amount, 7)
+        .accounts({
+          slasher: owner.publicKey,
+          user: user1.publicKey,
+        })
+        .signers([owner])
+        .rpc();
+    const executeSlash = () =>
+      program.methods
+        .executeSlash()
+        .accounts({
+          user: user1.publicKey,
+          tokenMint: tokenMint,
+          slashDestination: owner_ata,
+          userReceiptAccount: user1_receipt_ata,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const before = await program.account.userInfo.fetch(userInfo_pda);
+    const receiptsBefore = await receipts();
+    const amount = before.amount.divn(100);
+
+    await slash(amount);
+
+    let info = await program.account.userInfo.fetch(userInfo_pda);
+    assert.equal(info.amount.toString(), before.amount.toString());
+    assert.equal(info.pendingSlashAmount.toString(), amount.toString());
+
+    try {
+      await slash(amount);
+      assert.fail("a second slash while one is pending should fail");
+    } catch (err) {
+      assert.include(err.toString(), "SlashPending");
+    }
+
+    try {
+      await executeSlash();
+      assert.fail("executing inside the appeal window should fail");
+    } catch (err) {
+      assert.include(err.toString(), "AppealPending");
+    }
+
+    // the position can't run away from the slash
+    try {
+      await program.methods
+        .unStake()
+        .accounts({
+          user: user1.publicKey,
+          tokenMint: tokenMint,
+          tokenPgm: TOKEN_PROGRAM_ID,
+        })
+        .signers([user1])
+        .rpc();
+      assert.fail("unstaking with a pending slash should fail");
+    } catch (err) {
+      assert.include(err.toString(), "SlashPending");
+    }
+
+    while (
+      (await provider.connection.getSlot()) < info.appealUntilSlot.toNumber()
+    ) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+
+    await executeSlash();
+
+    info = await program.account.userInfo.fetch(userInfo_pda);
+    assert.equal(info.amount.toString(), before.amount.sub(amount).toString());
+    assert.equal(info.pendingSlashAmount.toNumber(), 0);
+    // receipts stay 1:1 with the position
+    assert.equal(
+      (await receipts()).toString(),
+      (receiptsBefore - BigInt(amount.toString())).toString()
+    );
+
+    // an upheld appeal drops the slash
+    await slash(amount);
+    await program.methods
+      .cancelSlash()
+      .accounts({
+        authority: owner.publicKey,
+        user: user1.publicKey,
+      })
+      .signers([owner])
+      .rpc();
+
+    const cancelled = await program.account.userInfo.fetch(userInfo_pda);
+    assert.equal(cancelled.amount.toString(), info.amount.toString());
+    assert.equal(cancelled.pendingSlashAmount.toNumber(), 0);
+  });
+
+  it("lets a relayer open a position for a wallet without SOL", async () => {
+    user3 = anchor.web3.Keypair.generate();
+
+    const user3_ata = await createAssociatedTokenAccount(
+      provider.connection,
+      owner,
+      tokenMint,
+      user3.publicKey
+    );
+    await transfer(
+      provider.connection,
+      user1,
+      user1_ata,
+      user3_ata,
+      user1,
+      10 ** 9
+    );
+
+    // owner pays the rent and the fee, user3 only signs as authority
+    await program.methods
+      .stake(new anchor.BN(10 ** 9), [])
+      .accounts({
+        feePayer: owner.publicKey,
+        user: user3.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner, user3])
+      .rpc();
+
+    const [user3_info] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("user-info"), user3.publicKey.toBuffer()],
+      program.programId
+    );
+    const info = await program.account.userInfo.fetch(user3_info);
+
+    assert.equal(info.amount.toNumber(), 10 ** 9);
+    assert.equal(await provider.connection.getBalance(user3.publicKey), 0);
+  });
+
+  it("unstakes without paying the reward streams", async () => {
+    const [user3_info] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("user-info"), user3.publicKey.toBuffer()],
+      program.programId
+    );
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    // no stream accounts, the principal comes back and the stream rewards stay owed
+    await program.methods
+      .unStake()
+      .accounts({
+        user: user3.publicKey,
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .signers([user3])
+      .rpc();
+
+    const balance = (
+      await getAccount(
+        provider.connection,
+        getAssociatedTokenAddressSync(tokenMint, user3.publicKey)
+      )
+    ).amount;
+    const info = await program.account.userInfo.fetch(user3_info);
+
+    assert.isTrue(balance >= BigInt(10 ** 9));
+    assert.equal(info.amount.toNumber(), 0);
+    assert.isAbove(info.streamOwed[0].toNumber(), 0);
+  });
+
+  it("returns the principal after the mint authority is released", async () => {
+    const user3_ata = getAssociatedTokenAddressSync(tokenMint, user3.publicKey);
+
+    await program.methods
+      .stake(new anchor.BN(10 ** 9), [])
+      .accounts({
+        feePayer: owner.publicKey,
+        user: user3.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner, user3])
+      .rpc();
+
+    const balanceBefore = (await getAccount(provider.connection, user3_ata))
+      .amount;
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    await program.methods
+      .executeAdminAction({ releaseMintAuthority: { newAuthority: null } })
+      .accounts({
+        owner: owner.publicKey,
+        tokenMint: tokenMint,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    assert.isNull(
+      (await getMint(provider.connection, tokenMint)).mintAuthority
+    );
+
+    // the rewards earned so far are forfeited, the principal still comes back
+    await program.methods
+      .unStake()
+      .accounts({
+        user: user3.publicKey,
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .signers([user3])
+      .rpc();
+
+    const balanceAfter = (await getAccount(provider.connection, user3_ata))
+      .amount;
+
+    assert.equal(
+      (balanceAfter - balanceBefore).toString(),
+      (10 ** 9).toString()
+    );
+  });
+
+  it("runs airdrops and reward streams through multisig proposals", async () => {
+    const [multisig_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("multisig")],
+      program.programId
+    );
+    const proposalPda = (index: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("proposal"),
+          new anchor.BN(index).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    // owner + user1, both have to approve
+    await program.methods
+      .createMultisig([owner.publicKey, user1.publicKey], 2)
+      .accounts({ owner: owner.publicKey })
+      .signers([owner])
+      .rpc();
+
+    const propose = async (index: number, action: any) => {
+      await program.methods
+        .propose(action)
+        .accountsPartial({
+          proposer: owner.publicKey,
+          proposal: proposalPda(index),
+        })
+        .signers([owner])
+        .rpc();
+      await program.methods
+        .approve()
+        .accountsPartial({
+          signer: user1.publicKey,
+          proposal: proposalPda(index),
+        })
+        .signers([user1])
+        .rpc();
+    };
+
+    const season = new anchor.BN(2);
+    const amount = new anchor.BN(10 ** 6);
+    const root = Array.from(createHash("sha256").update("season 2").digest());
+    const clawbackSlot = new anchor.BN(
+      (await provider.connection.getSlot()) + 20
+    );
+    const [distributor_pda] = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("distributor"), season.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+
+    const createDistributor = (proposal: anchor.web3.PublicKey | null) =>
+      program.methods
+        .createDistributor(
+          season,
+          root,
+          amount,
+          new anchor.BN(1),
+          clawbackSlot
+        )
+        .accountsPartial({
+          funder: owner.publicKey,
+          multisig: proposal ? multisig_pda : null,
+          proposal,
+          tokenMint: tokenMint,
+          funderAta: owner_ata,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([owner])
+        .rpc();
+
+    try {
+      await createDistributor(null);
+      assert.fail("the owner alone shouldn't be able to create a distributor");
+    } catch (err) {
+      assert.include(err.toString(), "ProposalRequired");
+    }
+
+    await propose(0, {
+      createDistributor: {
+        season,
+        root,
+        maxTotalClaim: amount,
+        maxNumNodes: new anchor.BN(1),
+        clawbackSlot,
+      },
+    });
+    await createDistributor(proposalPda(0));
+
+    await propose(1, {
+      clawback: { season, destination: owner_ata },
+    });
+
+    while ((await provider.connection.getSlot()) < clawbackSlot.toNumber()) {
+      await new Promise((resolve) => setTimeout(resolve, 500));
+    }
+
+    const balanceBefore = (await getAccount(provider.connection, owner_ata))
+      .amount;
+
+    await program.methods
+      .clawback()
+      .accountsPartial({
+        authority: owner.publicKey,
+        multisig: multisig_pda,
+        proposal: proposalPda(1),
+        distributor: distributor_pda,
+        tokenMint: tokenMint,
+        destination: owner_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    const balanceAfter = (await getAccount(provider.connection, owner_ata))
+      .amount;
+
+    assert.equal(
+      (balanceAfter - balanceBefore).toString(),
+      amount.toString()
+    );
+
+    // partner streams need the multisig's approval as well
+    const funder_ata = getAssociatedTokenAddressSync(
+      partnerMint,
+      owner.publicKey
+    );
+    await mintTo(
+      provider.connection,
+      owner,
+      partnerMint,
+      funder_ata,
+      owner,
+      10 ** 6
+    );
+
+    const startSlot = new anchor.BN(await provider.connection.getSlot());
+    const endSlot = startSlot.addn(1000);
+
+    await propose(2, {
+      addRewardStream: {
+        index: 1,
+        rewardMint: partnerMint,
+        funder: owner.publicKey,
+        budget: new anchor.BN(10 ** 6),
+        startSlot,
+        endSlot,
+      },
+    });
+
+    await program.methods
+      .addRewardStream(1, new anchor.BN(10 ** 6), startSlot, endSlot)
+      .accountsPartial({
+        owner: null,
+        funder: owner.publicKey,
+        multisig: multisig_pda,
+        proposal: proposalPda(2),
+        tokenMint: tokenMint,
+        rewardMint: partnerMint,
+        funderAta: funder_ata,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([owner])
+      .rpc();
+
+    const streams = await program.account.rewardStreams.fetch(
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("reward-streams")],
+        program.programId
+      )[0]
+    );
+    assert.equal(streams.streams.length, 2);
+
+    // streams can be claimed one at a time, the new one stays owed
+    const user1_partner_ata = getAssociatedTokenAddressSync(
+      partnerMint,
+      user1.publicKey
+    );
+    const partnerBefore = (
+      await getAccount(provider.connection, user1_partner_ata)
+    ).amount;
+
+    await new Promise((resolve) => setTimeout(resolve, 2000));
+
+    await program.methods
+      .claimPoints()
+      .accounts({
+        user: user1.publicKey,
+        tokenMint: tokenMint,
+        tokenPgm: TOKEN_PROGRAM_ID,
+      })
+      .remainingAccounts([
+        { pubkey: streams.streams[0].vault, isSigner: false, isWritable: true },
+        { pubkey: user1_partner_ata, isSigner: false, isWritable: true },
+        { pubkey: partnerMint, isSigner: false, isWritable: false },
+      ])
+      .signers([user1])
+      .rpc();
+
+    const partnerAfter = (
+      await getAccount(provider.connection, user1_partner_ata)
+    ).amount;
+    assert.isTrue(partnerAfter > partnerBefore);
+  });
 });